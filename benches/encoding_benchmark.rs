@@ -1,7 +1,7 @@
 use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
 use peak_alloc::PeakAlloc;
 use std::hint::black_box;
-use tiktoken_rust::{encoding_for_model, get_encoding};
+use tiktoken_rust::{Encoding, encoding_for_model, get_encoding};
 
 #[global_allocator]
 static PEAK_ALLOC: PeakAlloc = PeakAlloc;
@@ -318,6 +318,28 @@ fn bench_memory_efficiency(c: &mut Criterion) {
         })
     });
 
+    group.bench_function("encoding_creation_from_binary", |b| {
+        let source = get_encoding("cl100k_base").unwrap();
+        let mut blob = Vec::new();
+        source.serialize_to(&mut blob).unwrap();
+
+        b.iter_custom(|iters| {
+            PEAK_ALLOC.reset_peak_usage();
+
+            let start = std::time::Instant::now();
+            for _ in 0..iters {
+                let enc = Encoding::load_from(blob.as_slice()).unwrap();
+                black_box(enc);
+            }
+            let duration = start.elapsed();
+
+            let peak_memory = PEAK_ALLOC.peak_usage_as_mb();
+            println!("Peak memory for encoding creation from binary: {peak_memory:.2} MB");
+
+            duration
+        })
+    });
+
     group.bench_function("batch_vs_individual", |b| {
         let texts: Vec<&str> = (0..100).map(|_| text.as_str()).collect();
 
@@ -342,6 +364,109 @@ fn bench_memory_efficiency(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_reuse_buf(c: &mut Criterion) {
+    let enc = get_encoding("cl100k_base").unwrap();
+    let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.";
+
+    let mut group = c.benchmark_group("reuse_buf");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+
+    group.bench_function("encode_ordinary_fresh_vec", |b| {
+        b.iter(|| {
+            let tokens = enc.encode_ordinary(black_box(text));
+            black_box(tokens)
+        })
+    });
+
+    group.bench_function("encode_ordinary_into_reused_vec", |b| {
+        let mut tokens = Vec::new();
+        b.iter(|| {
+            tokens.clear();
+            enc.encode_ordinary_into(black_box(text), &mut tokens);
+            black_box(&tokens)
+        })
+    });
+
+    let tokens = enc.encode_ordinary(text);
+    group.bench_function("decode_bytes_fresh_vec", |b| {
+        b.iter(|| {
+            let bytes = enc.decode_bytes(black_box(&tokens)).unwrap();
+            black_box(bytes)
+        })
+    });
+
+    group.bench_function("decode_into_reused_vec", |b| {
+        let mut out = Vec::new();
+        b.iter(|| {
+            out.clear();
+            enc.decode_into(black_box(&tokens), &mut out).unwrap();
+            black_box(&out)
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_count(c: &mut Criterion) {
+    let enc = get_encoding("cl100k_base").unwrap();
+    let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.".repeat(10);
+
+    let mut group = c.benchmark_group("count");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+
+    group.bench_function("encode_ordinary_len", |b| {
+        b.iter(|| {
+            let count = enc.encode_ordinary(black_box(&text)).len();
+            black_box(count)
+        })
+    });
+
+    group.bench_function("count_ordinary", |b| {
+        b.iter(|| {
+            let count = enc.count_ordinary(black_box(&text));
+            black_box(count)
+        })
+    });
+
+    group.bench_function("encode_ordinary_len_peak_memory", |b| {
+        b.iter_custom(|iters| {
+            PEAK_ALLOC.reset_peak_usage();
+
+            let start = std::time::Instant::now();
+            for _ in 0..iters {
+                let count = enc.encode_ordinary(black_box(&text)).len();
+                black_box(count);
+            }
+            let duration = start.elapsed();
+
+            let peak_memory = PEAK_ALLOC.peak_usage_as_mb();
+            println!("Peak memory for encode_ordinary().len(): {peak_memory:.2} MB");
+
+            duration
+        })
+    });
+
+    group.bench_function("count_ordinary_peak_memory", |b| {
+        b.iter_custom(|iters| {
+            PEAK_ALLOC.reset_peak_usage();
+
+            let start = std::time::Instant::now();
+            for _ in 0..iters {
+                let count = enc.count_ordinary(black_box(&text));
+                black_box(count);
+            }
+            let duration = start.elapsed();
+
+            let peak_memory = PEAK_ALLOC.peak_usage_as_mb();
+            println!("Peak memory for count_ordinary(): {peak_memory:.2} MB");
+
+            duration
+        })
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_encoding_short_text,
@@ -353,6 +478,8 @@ criterion_group!(
     bench_special_tokens,
     bench_memory_usage,
     bench_cpu_intensive_operations,
-    bench_memory_efficiency
+    bench_memory_efficiency,
+    bench_reuse_buf,
+    bench_count
 );
 criterion_main!(benches);
@@ -0,0 +1,225 @@
+//! A small, self-describing tagged binary format used to serialize
+//! [`crate::core::Encoding`] definitions.
+//!
+//! Every value starts with a tag byte - `B` (binary blob), `N` (natural
+//! number), `L` (list), or `R` (record) - followed by a decimal number (the
+//! byte count for blobs, the item count for lists, the field count for
+//! records, the value itself for naturals) and the payload, e.g.
+//! `B12:hello world!,` for a 12-byte blob. Lists and records carry no byte
+//! length, so a reader cannot jump over one by offset; it skips a record
+//! field it doesn't recognize by parsing that field's value generically and
+//! ignoring it, so new fields can be added without breaking old readers.
+
+use crate::errors::{Result, TiktokenError};
+
+/// A value in the tagged binary format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TaggedValue {
+    Blob(Vec<u8>),
+    Natural(u64),
+    List(Vec<TaggedValue>),
+    Record(Vec<(String, TaggedValue)>),
+}
+
+impl TaggedValue {
+    pub(crate) fn blob(bytes: impl Into<Vec<u8>>) -> Self {
+        TaggedValue::Blob(bytes.into())
+    }
+
+    /// Append this value's encoded bytes to `out`.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            TaggedValue::Blob(bytes) => {
+                out.extend_from_slice(format!("B{}:", bytes.len()).as_bytes());
+                out.extend_from_slice(bytes);
+                out.push(b',');
+            }
+            TaggedValue::Natural(n) => {
+                out.extend_from_slice(format!("N{n}").as_bytes());
+                out.push(b',');
+            }
+            TaggedValue::List(items) => {
+                out.extend_from_slice(format!("L{}:", items.len()).as_bytes());
+                for item in items {
+                    item.write(out);
+                }
+                out.push(b',');
+            }
+            TaggedValue::Record(fields) => {
+                out.extend_from_slice(format!("R{}:", fields.len()).as_bytes());
+                for (key, value) in fields {
+                    TaggedValue::blob(key.as_bytes().to_vec()).write(out);
+                    value.write(out);
+                }
+                out.push(b',');
+            }
+        }
+    }
+
+    /// Encode this value as a standalone byte vector.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write(&mut out);
+        out
+    }
+
+    /// Parse a single tagged value starting at `*pos`, advancing `*pos` past
+    /// it.
+    pub(crate) fn parse(data: &[u8], pos: &mut usize) -> Result<Self> {
+        let tag = *data
+            .get(*pos)
+            .ok_or_else(|| TiktokenError::DataError("Unexpected end of tagged data".to_string()))?;
+        *pos += 1;
+
+        match tag {
+            b'B' => {
+                let len = read_decimal(data, pos, b':')? as usize;
+                let end = pos.checked_add(len).ok_or_else(|| {
+                    TiktokenError::DataError("Blob length overflowed".to_string())
+                })?;
+                let bytes = data
+                    .get(*pos..end)
+                    .ok_or_else(|| TiktokenError::DataError("Blob ran past end of data".to_string()))?
+                    .to_vec();
+                *pos = end;
+                expect(data, pos, b',')?;
+                Ok(TaggedValue::Blob(bytes))
+            }
+            b'N' => {
+                let n = read_decimal(data, pos, b',')?;
+                Ok(TaggedValue::Natural(n))
+            }
+            b'L' => {
+                let count = read_decimal(data, pos, b':')?;
+                let mut items = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    items.push(TaggedValue::parse(data, pos)?);
+                }
+                expect(data, pos, b',')?;
+                Ok(TaggedValue::List(items))
+            }
+            b'R' => {
+                let count = read_decimal(data, pos, b':')?;
+                let mut fields = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let key = match TaggedValue::parse(data, pos)? {
+                        TaggedValue::Blob(bytes) => String::from_utf8(bytes)
+                            .map_err(|e| TiktokenError::DataError(e.to_string()))?,
+                        _ => {
+                            return Err(TiktokenError::DataError(
+                                "Record field name must be a blob".to_string(),
+                            ));
+                        }
+                    };
+                    let value = TaggedValue::parse(data, pos)?;
+                    fields.push((key, value));
+                }
+                expect(data, pos, b',')?;
+                Ok(TaggedValue::Record(fields))
+            }
+            other => Err(TiktokenError::DataError(format!(
+                "Unknown tagged-value tag byte: {:?}",
+                other as char
+            ))),
+        }
+    }
+
+    /// Parse `data` as a single standalone tagged value, requiring the
+    /// entire slice to be consumed.
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+        let value = TaggedValue::parse(data, &mut pos)?;
+        if pos != data.len() {
+            return Err(TiktokenError::DataError("Trailing bytes after tagged value".to_string()));
+        }
+        Ok(value)
+    }
+
+    /// Look up a field by name if this value is a record.
+    pub(crate) fn field(&self, name: &str) -> Option<&TaggedValue> {
+        match self {
+            TaggedValue::Record(fields) => {
+                fields.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_blob(&self) -> Option<&[u8]> {
+        match self {
+            TaggedValue::Blob(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_natural(&self) -> Option<u64> {
+        match self {
+            TaggedValue::Natural(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_list(&self) -> Option<&[TaggedValue]> {
+        match self {
+            TaggedValue::List(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+fn read_decimal(data: &[u8], pos: &mut usize, delim: u8) -> Result<u64> {
+    let start = *pos;
+    while data.get(*pos).copied() != Some(delim) {
+        if *pos >= data.len() {
+            return Err(TiktokenError::DataError("Unterminated length prefix".to_string()));
+        }
+        *pos += 1;
+    }
+
+    let digits = std::str::from_utf8(&data[start..*pos])
+        .map_err(|e| TiktokenError::DataError(e.to_string()))?;
+    let n: u64 = digits.parse().map_err(|e: std::num::ParseIntError| {
+        TiktokenError::DataError(format!("Invalid length prefix '{digits}': {e}"))
+    })?;
+    *pos += 1; // consume delimiter
+    Ok(n)
+}
+
+fn expect(data: &[u8], pos: &mut usize, byte: u8) -> Result<()> {
+    if data.get(*pos).copied() != Some(byte) {
+        return Err(TiktokenError::DataError("Malformed tagged value".to_string()));
+    }
+    *pos += 1;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_roundtrip() {
+        let value = TaggedValue::blob(b"hello world!".to_vec());
+        let bytes = value.to_bytes();
+        assert_eq!(bytes, b"B12:hello world!,");
+        assert_eq!(TaggedValue::from_bytes(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn test_record_roundtrip() {
+        let value = TaggedValue::Record(vec![
+            ("name".to_string(), TaggedValue::blob(b"cl100k_base".to_vec())),
+            ("rank".to_string(), TaggedValue::Natural(42)),
+            (
+                "children".to_string(),
+                TaggedValue::List(vec![TaggedValue::Natural(1), TaggedValue::Natural(2)]),
+            ),
+        ]);
+
+        let bytes = value.to_bytes();
+        let parsed = TaggedValue::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, value);
+        assert_eq!(parsed.field("rank").and_then(TaggedValue::as_natural), Some(42));
+        assert_eq!(parsed.field("unknown_field"), None);
+    }
+}
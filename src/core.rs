@@ -1,5 +1,6 @@
 //! Core BPE implementation and encoding structures
 
+use crate::binary_format::TaggedValue;
 use crate::errors::{Result, TiktokenError};
 use regex::Regex;
 use std::collections::HashMap;
@@ -10,6 +11,51 @@ pub type Token = u32;
 /// Rank type for BPE merge priorities
 pub type Rank = u32;
 
+/// The regex backend used to split text into pre-tokenization pieces.
+///
+/// The `regex` crate is linear-time but, by design, cannot compile patterns
+/// that use lookaround or backreferences - which the official cl100k_base
+/// and o200k_base split patterns rely on (e.g. `\s+(?!\S)`). Rather than
+/// simplify those patterns and silently diverge from upstream tiktoken, we
+/// fall back to `fancy_regex`, which supports them at the cost of potential
+/// backtracking.
+#[derive(Clone)]
+enum SplitRegex {
+    Standard(Regex),
+    Fancy(fancy_regex::Regex),
+}
+
+impl SplitRegex {
+    /// Compile `pattern`, preferring the linear-time `regex` backend and
+    /// only falling back to `fancy_regex` if `pattern` uses a construct
+    /// `regex` rejects.
+    fn new(pattern: &str) -> Result<Self> {
+        match Regex::new(pattern) {
+            Ok(re) => Ok(SplitRegex::Standard(re)),
+            Err(_) => {
+                let re = fancy_regex::Regex::new(pattern)
+                    .map_err(|e| TiktokenError::RegexError(e.to_string()))?;
+                Ok(SplitRegex::Fancy(re))
+            }
+        }
+    }
+
+    /// Iterate over the matched pieces of `text`, in order. Fancy-regex
+    /// match errors (e.g. catastrophic backtracking limits) are skipped
+    /// rather than propagated, matching `find_iter`'s infallible shape.
+    fn find_iter<'r, 't>(&'r self, text: &'t str) -> Box<dyn Iterator<Item = &'t str> + 'r>
+    where
+        't: 'r,
+    {
+        match self {
+            SplitRegex::Standard(re) => Box::new(re.find_iter(text).map(|m| m.as_str())),
+            SplitRegex::Fancy(re) => {
+                Box::new(re.find_iter(text).filter_map(|m| m.ok()).map(|m| m.as_str()))
+            }
+        }
+    }
+}
+
 /// Core BPE encoder/decoder with all necessary data
 #[derive(Clone)]
 pub struct CoreBPE {
@@ -22,9 +68,13 @@ pub struct CoreBPE {
     /// Maps special token IDs to their byte sequences
     special_tokens_decoder: HashMap<Token, Vec<u8>>,
     /// Regex for splitting text into pieces
-    regex: Regex,
+    regex: SplitRegex,
     /// Regex for finding special tokens
     special_regex: Option<Regex>,
+    /// Source pattern string `regex` was compiled from, kept around for
+    /// serialization since `Regex` itself cannot be reconstructed from
+    /// nothing but its compiled form.
+    pattern: String,
 }
 
 impl CoreBPE {
@@ -34,7 +84,7 @@ impl CoreBPE {
         special_tokens: HashMap<String, Token>,
         pattern: &str,
     ) -> Result<Self> {
-        let regex = Regex::new(pattern)?;
+        let regex = SplitRegex::new(pattern)?;
 
         // Build encoder from mergeable ranks
         let encoder: HashMap<Vec<u8>, Token> = mergeable_ranks;
@@ -65,26 +115,143 @@ impl CoreBPE {
             special_tokens_decoder,
             regex,
             special_regex,
+            pattern: pattern.to_string(),
         })
     }
 
     /// Encode text to tokens, ignoring special tokens
     pub fn encode_ordinary(&self, text: &str) -> Vec<Token> {
         let mut tokens = Vec::new();
+        self.encode_ordinary_into(text, &mut tokens);
+        tokens
+    }
 
-        for mat in self.regex.find_iter(text) {
-            let piece = mat.as_str().as_bytes();
+    /// Encode text to tokens, ignoring special tokens, appending to `out`
+    /// instead of allocating a new `Vec`. Lets a caller reuse one buffer
+    /// across many calls instead of paying an allocation per call.
+    pub fn encode_ordinary_into(&self, text: &str, out: &mut Vec<Token>) {
+        for piece_str in self.regex.find_iter(text) {
+            let piece = piece_str.as_bytes();
 
             // Check if this piece is a single token
             if let Some(&token) = self.encoder.get(piece) {
-                tokens.push(token);
+                out.push(token);
             } else {
                 // Apply BPE to this piece
+                out.extend(self.byte_pair_encode(piece));
+            }
+        }
+    }
+
+    /// Encode text to tokens, ignoring special tokens, but stop and error as
+    /// soon as `max` tokens would be exceeded instead of encoding the whole
+    /// input.
+    pub fn encode_ordinary_with_limit(&self, text: &str, max: usize) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+
+        for piece_str in self.regex.find_iter(text) {
+            let piece = piece_str.as_bytes();
+
+            if let Some(&token) = self.encoder.get(piece) {
+                tokens.push(token);
+            } else {
                 tokens.extend(self.byte_pair_encode(piece));
             }
+
+            if tokens.len() > max {
+                return Err(TiktokenError::EncodingError(format!(
+                    "Token limit of {max} exceeded"
+                )));
+            }
         }
 
-        tokens
+        Ok(tokens)
+    }
+
+    /// Count the tokens `encode_ordinary` would produce, without allocating
+    /// the token `Vec`: runs the same BPE merge as `encode_ordinary` for each
+    /// piece, but only counts the resulting boundaries instead of collecting
+    /// their ranks into a `Vec`.
+    pub fn count_ordinary(&self, text: &str) -> usize {
+        let mut count = 0;
+        for piece_str in self.regex.find_iter(text) {
+            let piece = piece_str.as_bytes();
+
+            if self.encoder.contains_key(piece) {
+                count += 1;
+            } else {
+                count += self.byte_pair_encode_count(piece);
+            }
+        }
+        count
+    }
+
+    /// Count the tokens `encode` would produce, without allocating the token
+    /// `Vec`.
+    pub fn count(
+        &self,
+        text: &str,
+        allowed_special: &[&str],
+        disallowed_special: &[&str],
+    ) -> Result<usize> {
+        if !disallowed_special.is_empty() {
+            if let Some(ref special_regex) = self.special_regex {
+                for mat in special_regex.find_iter(text) {
+                    let token_text = mat.as_str();
+                    if disallowed_special.contains(&token_text)
+                        && !allowed_special.contains(&token_text)
+                    {
+                        return Err(TiktokenError::EncodingError(format!(
+                            "Disallowed special token: {token_text}"
+                        )));
+                    }
+                }
+            }
+        }
+
+        let mut count = 0;
+        let mut start = 0;
+
+        while start < text.len() {
+            let mut next_special_start = text.len();
+            let mut next_special_end = text.len();
+            let mut found_special = None;
+
+            if let Some(ref special_regex) = self.special_regex {
+                for mat in special_regex.find_iter(&text[start..]) {
+                    let token_text = &text[start + mat.start()..start + mat.end()];
+                    if allowed_special.contains(&token_text) {
+                        next_special_start = start + mat.start();
+                        next_special_end = start + mat.end();
+                        found_special = Some(token_text);
+                        break;
+                    }
+                }
+            }
+
+            if next_special_start > start {
+                count += self.count_ordinary(&text[start..next_special_start]);
+            }
+
+            if let Some(special_token) = found_special {
+                if self.special_tokens_encoder.contains_key(special_token) {
+                    count += 1;
+                }
+                start = next_special_end;
+            } else {
+                break;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Byte offsets where each regex-split piece of `text` begins, in order.
+    /// Used by the streaming reader to tell which pieces are complete (a new
+    /// piece has started after them) versus still possibly growing with more
+    /// input (the last piece found so far).
+    pub(crate) fn piece_starts(&self, text: &str) -> Vec<usize> {
+        self.regex.find_iter(text).map(|piece| piece.as_ptr() as usize - text.as_ptr() as usize).collect()
     }
 
     /// Encode text to tokens with special token handling
@@ -94,6 +261,20 @@ impl CoreBPE {
         allowed_special: &[&str],
         disallowed_special: &[&str],
     ) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        self.encode_into(text, allowed_special, disallowed_special, &mut tokens)?;
+        Ok(tokens)
+    }
+
+    /// Encode text to tokens with special token handling, appending to `out`
+    /// instead of allocating a new `Vec`.
+    pub fn encode_into(
+        &self,
+        text: &str,
+        allowed_special: &[&str],
+        disallowed_special: &[&str],
+        out: &mut Vec<Token>,
+    ) -> Result<()> {
         // Check for disallowed special tokens
         if !disallowed_special.is_empty() {
             if let Some(ref special_regex) = self.special_regex {
@@ -110,7 +291,6 @@ impl CoreBPE {
             }
         }
 
-        let mut tokens = Vec::new();
         let mut start = 0;
 
         // Process text, handling special tokens
@@ -135,13 +315,13 @@ impl CoreBPE {
             // Encode the text before the special token
             if next_special_start > start {
                 let ordinary_text = &text[start..next_special_start];
-                tokens.extend(self.encode_ordinary(ordinary_text));
+                self.encode_ordinary_into(ordinary_text, out);
             }
 
             // Add the special token if found
             if let Some(special_token) = found_special {
                 if let Some(&token) = self.special_tokens_encoder.get(special_token) {
-                    tokens.push(token);
+                    out.push(token);
                 }
                 start = next_special_end;
             } else {
@@ -150,24 +330,30 @@ impl CoreBPE {
             }
         }
 
-        Ok(tokens)
+        Ok(())
     }
 
     /// Decode tokens back to bytes
     pub fn decode_bytes(&self, tokens: &[Token]) -> Result<Vec<u8>> {
         let mut result = Vec::new();
+        self.decode_bytes_into(tokens, &mut result)?;
+        Ok(result)
+    }
 
+    /// Decode tokens back to bytes, appending to `out` instead of allocating
+    /// a new `Vec`.
+    pub fn decode_bytes_into(&self, tokens: &[Token], out: &mut Vec<u8>) -> Result<()> {
         for &token in tokens {
             if let Some(bytes) = self.decoder.get(&token) {
-                result.extend_from_slice(bytes);
+                out.extend_from_slice(bytes);
             } else if let Some(bytes) = self.special_tokens_decoder.get(&token) {
-                result.extend_from_slice(bytes);
+                out.extend_from_slice(bytes);
             } else {
                 return Err(TiktokenError::InvalidToken(token));
             }
         }
 
-        Ok(result)
+        Ok(())
     }
 
     /// Decode tokens back to string
@@ -204,13 +390,115 @@ impl CoreBPE {
             .collect()
     }
 
-    /// Core BPE merge algorithm
+    /// Like `byte_pair_encode`, but runs `byte_pair_merge` and returns only
+    /// the resulting boundary count instead of looking up each window's rank
+    /// and collecting them into a `Vec`. Saves the final token `Vec` and its
+    /// per-window encoder lookups; the merge itself (and its own heap/linked-
+    /// list allocations) still runs in full.
+    fn byte_pair_encode_count(&self, piece: &[u8]) -> usize {
+        if piece.len() == 1 {
+            return 1;
+        }
+
+        self.byte_pair_merge(piece).len() - 1
+    }
+
+    /// Core BPE merge algorithm.
+    ///
+    /// Maintains the remaining piece boundaries as a doubly-linked list over
+    /// the original byte offsets (`prev`/`next`), so merging two neighbors is
+    /// an O(1) unlink instead of a `Vec::remove`. A `BinaryHeap` of
+    /// `Reverse((rank, start_pos, version))` entries tracks the next merge
+    /// candidate; a `version` counter per position lets us recognize and
+    /// discard stale entries left behind by earlier merges in O(1) rather
+    /// than rescanning all remaining pairs for the new minimum. Ordering by
+    /// `(rank, start_pos)` preserves tiktoken's "lowest rank, leftmost on
+    /// ties" tie-break, so the returned boundaries are bit-identical to the
+    /// straightforward rescan approach.
     fn byte_pair_merge(&self, piece: &[u8]) -> Vec<(usize, Rank)> {
-        // This is a vector of (start, rank).
-        // The rank is of the pair starting at position start.
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+
+        let n = piece.len();
+
+        // next[i]/prev[i] point to the neighboring *live* boundary for
+        // position i. Position n is the end-of-piece sentinel and is never
+        // merged away.
+        let mut next: Vec<usize> = (0..=n).map(|i| i + 1).collect();
+        let mut prev: Vec<usize> = (0..=n).map(|i| i.wrapping_sub(1)).collect();
+        let mut removed = vec![false; n];
+        let mut version = vec![0u32; n];
+
+        let pair_rank = |piece: &[u8], pos: usize, next: &[usize]| -> Rank {
+            let mid = next[pos];
+            if mid >= n {
+                return Rank::MAX;
+            }
+            let end = next[mid];
+            self.encoder.get(&piece[pos..end]).copied().unwrap_or(Rank::MAX)
+        };
+
+        let mut heap = BinaryHeap::new();
+        for i in 0..n {
+            let rank = pair_rank(piece, i, &next);
+            if rank != Rank::MAX {
+                heap.push(Reverse((rank, i, version[i])));
+            }
+        }
+
+        while let Some(Reverse((_rank, pos, ver))) = heap.pop() {
+            if removed[pos] || version[pos] != ver {
+                continue; // stale entry, a neighbor already changed
+            }
+
+            let mid = next[pos];
+            if mid >= n {
+                continue; // no right neighbor left to merge with
+            }
+            let end = next[mid];
+
+            // Merge piece[pos..end) by unlinking `mid` from the list.
+            next[pos] = end;
+            if end <= n {
+                prev[end] = pos;
+            }
+            removed[mid] = true;
+            version[pos] += 1;
+
+            let new_rank = pair_rank(piece, pos, &next);
+            if new_rank != Rank::MAX {
+                heap.push(Reverse((new_rank, pos, version[pos])));
+            }
+
+            let left = prev[pos];
+            if left != usize::MAX {
+                version[left] += 1;
+                let left_rank = pair_rank(piece, left, &next);
+                if left_rank != Rank::MAX {
+                    heap.push(Reverse((left_rank, left, version[left])));
+                }
+            }
+        }
+
+        let mut parts = Vec::new();
+        let mut pos = 0;
+        loop {
+            parts.push((pos, Rank::MAX));
+            if pos >= n {
+                break;
+            }
+            pos = next[pos];
+        }
+        parts
+    }
+
+    /// Reference implementation of [`byte_pair_merge`](Self::byte_pair_merge)
+    /// that rescans all remaining pairs for the global minimum on every
+    /// merge. Kept only to cross-check the heap-based algorithm in tests.
+    #[cfg(test)]
+    fn byte_pair_merge_naive(&self, piece: &[u8]) -> Vec<(usize, Rank)> {
         let mut parts = Vec::with_capacity(piece.len() + 1);
 
-        // Find initial ranks for all adjacent pairs
         let mut min_rank = (Rank::MAX, usize::MAX);
         for i in 0..piece.len() - 1 {
             let pair = &piece[i..i + 2];
@@ -224,20 +512,16 @@ impl CoreBPE {
         parts.push((piece.len() - 1, Rank::MAX));
         parts.push((piece.len(), Rank::MAX));
 
-        // Iteratively merge the pair with the lowest rank
         while min_rank.0 != Rank::MAX {
             let i = min_rank.1;
 
-            // Update ranks for adjacent pairs before removing the middle element
             if i > 0 {
-                parts[i - 1].1 = self.get_pair_rank(piece, &parts, i - 1);
+                parts[i - 1].1 = self.get_pair_rank_naive(piece, &parts, i - 1);
             }
-            parts[i].1 = self.get_pair_rank(piece, &parts, i);
+            parts[i].1 = self.get_pair_rank_naive(piece, &parts, i);
 
-            // Remove the middle element
             parts.remove(i + 1);
 
-            // Find the new minimum rank
             min_rank = (Rank::MAX, usize::MAX);
             for (idx, &(_, rank)) in parts[..parts.len() - 1].iter().enumerate() {
                 if rank < min_rank.0 {
@@ -249,8 +533,9 @@ impl CoreBPE {
         parts
     }
 
-    /// Get the rank of a pair at a given position
-    fn get_pair_rank(&self, piece: &[u8], parts: &[(usize, Rank)], i: usize) -> Rank {
+    /// Get the rank of a pair at a given position (naive reference helper)
+    #[cfg(test)]
+    fn get_pair_rank_naive(&self, piece: &[u8], parts: &[(usize, Rank)], i: usize) -> Rank {
         if i + 3 < parts.len() {
             let start = parts[i].0;
             let end = parts[i + 3].0;
@@ -281,6 +566,35 @@ impl CoreBPE {
     pub fn vocab_size(&self) -> usize {
         self.decoder.len() + self.special_tokens_decoder.len()
     }
+
+    /// Get the mergeable ranks table (byte sequence -> token id)
+    pub(crate) fn mergeable_ranks(&self) -> &HashMap<Vec<u8>, Token> {
+        &self.encoder
+    }
+
+    /// Get the source pattern string used to split text into pieces
+    pub(crate) fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+/// Ordering used when assembling a fill-in-the-middle (FIM) prompt. See
+/// [`Encoding::encode_fim`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FimMode {
+    /// Prefix-suffix-middle: `fim_prefix <prefix> fim_suffix <suffix> fim_middle`.
+    Psm,
+    /// Suffix-prefix-middle: `fim_prefix fim_suffix <suffix> fim_middle <prefix>`.
+    Spm,
+}
+
+/// A single chat message to account tokens for, mirroring the shape of the
+/// OpenAI chat completions API. See [`Encoding::count_chat_tokens`].
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    pub name: Option<String>,
 }
 
 /// High-level encoding interface that wraps CoreBPE
@@ -304,11 +618,36 @@ impl Encoding {
         Ok(Encoding { name, core })
     }
 
+    /// Build an encoding from a standard `.tiktoken` rank file on disk.
+    ///
+    /// The file holds one entry per line of `<base64-token> <rank>`; this
+    /// parses it strictly, rejecting duplicate token bytes or ranks (unlike
+    /// the built-in encodings' loader, where a later line overwrites an
+    /// earlier one), so callers can bring their own BPE vocab without
+    /// recompiling.
+    pub fn from_tiktoken_file<P: AsRef<std::path::Path>>(
+        name: String,
+        path: P,
+        special_tokens: HashMap<String, Token>,
+        pattern: &str,
+    ) -> Result<Self> {
+        let mergeable_ranks = crate::vocab::load_tiktoken_file(path.as_ref())?;
+        Encoding::new(name, mergeable_ranks, special_tokens, pattern)
+    }
+
     /// Encode text to tokens, ignoring special tokens
     pub fn encode_ordinary(&self, text: &str) -> Vec<Token> {
         self.core.encode_ordinary(text)
     }
 
+    /// Encode text to tokens, ignoring special tokens, appending to `out`
+    /// instead of allocating a new `Vec`. Useful when encoding many texts in
+    /// a loop: reuse one buffer and `out.clear()` between calls instead of
+    /// allocating a fresh `Vec` each time.
+    pub fn encode_ordinary_into(&self, text: &str, out: &mut Vec<Token>) {
+        self.core.encode_ordinary_into(text, out)
+    }
+
     /// Encode text to tokens with special token handling
     pub fn encode(
         &self,
@@ -319,6 +658,44 @@ impl Encoding {
         self.core.encode(text, allowed_special, disallowed_special)
     }
 
+    /// Encode text to tokens with special token handling, appending to `out`
+    /// instead of allocating a new `Vec`.
+    pub fn encode_into(
+        &self,
+        text: &str,
+        allowed_special: &[&str],
+        disallowed_special: &[&str],
+        out: &mut Vec<Token>,
+    ) -> Result<()> {
+        self.core.encode_into(text, allowed_special, disallowed_special, out)
+    }
+
+    /// Count the tokens `encode_ordinary` would produce, without allocating
+    /// the token `Vec`. Use this instead of `encode_ordinary(text).len()`
+    /// when only the count is needed (e.g. context-window budgeting).
+    pub fn count_ordinary(&self, text: &str) -> usize {
+        self.core.count_ordinary(text)
+    }
+
+    /// Count the tokens `encode` would produce, without allocating the token
+    /// `Vec`.
+    pub fn count(
+        &self,
+        text: &str,
+        allowed_special: &[&str],
+        disallowed_special: &[&str],
+    ) -> Result<usize> {
+        self.core.count(text, allowed_special, disallowed_special)
+    }
+
+    /// Byte offsets where each regex-split piece of `text` begins. Exposed
+    /// crate-internally for the streaming reader, which needs to know piece
+    /// boundaries to decide what's safe to tokenize before more input
+    /// arrives.
+    pub(crate) fn piece_starts(&self, text: &str) -> Vec<usize> {
+        self.core.piece_starts(text)
+    }
+
     /// Decode tokens back to string
     pub fn decode(&self, tokens: &[Token]) -> Result<String> {
         self.core.decode(tokens)
@@ -329,6 +706,12 @@ impl Encoding {
         self.core.decode_bytes(tokens)
     }
 
+    /// Decode tokens back to bytes, appending to `out` instead of allocating
+    /// a new `Vec`.
+    pub fn decode_into(&self, tokens: &[Token], out: &mut Vec<u8>) -> Result<()> {
+        self.core.decode_bytes_into(tokens, out)
+    }
+
     /// Get the byte sequence for a single token
     pub fn decode_single_token_bytes(&self, token: Token) -> Result<&[u8]> {
         self.core.decode_single_token_bytes(token)
@@ -366,15 +749,28 @@ impl Encoding {
         allowed_special: &[&str],
         disallowed_special: &[&str],
     ) -> Result<Vec<Vec<Token>>> {
+        let mut scratch = Vec::new();
         texts
             .iter()
-            .map(|&text| self.encode(text, allowed_special, disallowed_special))
+            .map(|&text| {
+                scratch.clear();
+                self.encode_into(text, allowed_special, disallowed_special, &mut scratch)?;
+                Ok(scratch.clone())
+            })
             .collect()
     }
 
     /// Encode a batch of texts, ignoring special tokens
     pub fn encode_ordinary_batch(&self, texts: &[&str]) -> Vec<Vec<Token>> {
-        texts.iter().map(|&text| self.encode_ordinary(text)).collect()
+        let mut scratch = Vec::new();
+        texts
+            .iter()
+            .map(|&text| {
+                scratch.clear();
+                self.encode_ordinary_into(text, &mut scratch);
+                scratch.clone()
+            })
+            .collect()
     }
 
     /// Decode a batch of token sequences
@@ -384,7 +780,15 @@ impl Encoding {
 
     /// Decode a batch of token sequences to bytes
     pub fn decode_bytes_batch(&self, token_sequences: &[&[Token]]) -> Result<Vec<Vec<u8>>> {
-        token_sequences.iter().map(|&tokens| self.decode_bytes(tokens)).collect()
+        let mut scratch = Vec::new();
+        token_sequences
+            .iter()
+            .map(|&tokens| {
+                scratch.clear();
+                self.decode_into(tokens, &mut scratch)?;
+                Ok(scratch.clone())
+            })
+            .collect()
     }
 
     /// Encode a single token from text
@@ -418,4 +822,456 @@ impl Encoding {
     pub fn eot_token(&self) -> Option<Token> {
         self.special_tokens().get("<|endoftext|>").copied()
     }
+
+    /// Assemble a fill-in-the-middle (FIM) prompt from a `prefix` and
+    /// `suffix`, splicing in the `<|fim_prefix|>`/`<|fim_middle|>`/
+    /// `<|fim_suffix|>` sentinel tokens according to `mode`. Errors if this
+    /// encoding does not define all three FIM special tokens (only
+    /// `cl100k_base` and `p50k_edit` do).
+    pub fn encode_fim(&self, prefix: &str, suffix: &str, mode: FimMode) -> Result<Vec<Token>> {
+        let special_tokens = self.special_tokens();
+        let fim_prefix = *special_tokens.get("<|fim_prefix|>").ok_or_else(|| {
+            TiktokenError::EncodingError(format!(
+                "Encoding '{}' does not define <|fim_prefix|>",
+                self.name
+            ))
+        })?;
+        let fim_middle = *special_tokens.get("<|fim_middle|>").ok_or_else(|| {
+            TiktokenError::EncodingError(format!(
+                "Encoding '{}' does not define <|fim_middle|>",
+                self.name
+            ))
+        })?;
+        let fim_suffix = *special_tokens.get("<|fim_suffix|>").ok_or_else(|| {
+            TiktokenError::EncodingError(format!(
+                "Encoding '{}' does not define <|fim_suffix|>",
+                self.name
+            ))
+        })?;
+
+        let prefix_tokens = self.encode_ordinary(prefix);
+        let suffix_tokens = self.encode_ordinary(suffix);
+
+        let mut tokens = Vec::with_capacity(prefix_tokens.len() + suffix_tokens.len() + 3);
+        match mode {
+            FimMode::Psm => {
+                tokens.push(fim_prefix);
+                tokens.extend(prefix_tokens);
+                tokens.push(fim_suffix);
+                tokens.extend(suffix_tokens);
+                tokens.push(fim_middle);
+            }
+            FimMode::Spm => {
+                tokens.push(fim_prefix);
+                tokens.push(fim_suffix);
+                tokens.extend(suffix_tokens);
+                tokens.push(fim_middle);
+                tokens.extend(prefix_tokens);
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Serialize this encoding's name, pattern, special tokens, and ranks
+    /// into a self-describing, whitespace-tolerant text representation that
+    /// can be checked into version control and handed to
+    /// [`from_definition_text`](Self::from_definition_text) or
+    /// [`register_encoding`](crate::register_encoding).
+    ///
+    /// Layout: a `name = ...` line, a `pattern = ...` line, one
+    /// `special_token <name> <rank>` line per special token, a blank line,
+    /// then the rank table as base64-token/rank lines (the same body format
+    /// as a `.tiktoken` file). Token bytes round-trip exactly through base64
+    /// even when they aren't valid UTF-8.
+    pub fn to_definition_text(&self) -> String {
+        use base64::Engine;
+
+        let mut out = String::new();
+        out.push_str(&format!("name = {}\n", self.name));
+        out.push_str(&format!("pattern = {}\n", self.core.pattern()));
+
+        let mut special_tokens: Vec<(&String, &Token)> = self.core.special_tokens().iter().collect();
+        special_tokens.sort_by_key(|(_, &rank)| rank);
+        for (text, rank) in special_tokens {
+            out.push_str(&format!("special_token {text} {rank}\n"));
+        }
+        out.push('\n');
+
+        let mut ranks: Vec<(&Vec<u8>, &Token)> = self.core.mergeable_ranks().iter().collect();
+        ranks.sort_by_key(|(_, &rank)| rank);
+        for (bytes, rank) in ranks {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+            out.push_str(&format!("{encoded} {rank}\n"));
+        }
+
+        out
+    }
+
+    /// Reconstruct an `Encoding` previously serialized with
+    /// [`to_definition_text`](Self::to_definition_text). Returns a
+    /// `TiktokenError::DataError` if the header is missing required fields
+    /// or a rank/special-token line is malformed.
+    pub fn from_definition_text(text: &str) -> Result<Self> {
+        use base64::Engine;
+
+        let mut name: Option<String> = None;
+        let mut pattern: Option<String> = None;
+        let mut special_tokens = HashMap::new();
+        let mut ranks = HashMap::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(value) = line.strip_prefix("name = ") {
+                name = Some(value.to_string());
+            } else if let Some(value) = line.strip_prefix("pattern = ") {
+                pattern = Some(value.to_string());
+            } else if let Some(rest) = line.strip_prefix("special_token ") {
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let [token_name, rank] = parts[..] else {
+                    return Err(TiktokenError::DataError(format!(
+                        "Invalid special_token line {}: {}",
+                        line_no + 1,
+                        line
+                    )));
+                };
+                let rank: Token = rank.parse().map_err(|e| {
+                    TiktokenError::DataError(format!(
+                        "Invalid rank in special_token line {}: {e}",
+                        line_no + 1
+                    ))
+                })?;
+                special_tokens.insert(token_name.to_string(), rank);
+            } else {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                let [token_b64, rank] = parts[..] else {
+                    return Err(TiktokenError::DataError(format!(
+                        "Invalid rank line {}: {}",
+                        line_no + 1,
+                        line
+                    )));
+                };
+                let token_bytes = base64::engine::general_purpose::STANDARD
+                    .decode(token_b64)
+                    .map_err(|e| {
+                        TiktokenError::DataError(format!(
+                            "Invalid base64 at line {}: {e}",
+                            line_no + 1
+                        ))
+                    })?;
+                let rank: Rank = rank.parse().map_err(|e| {
+                    TiktokenError::DataError(format!(
+                        "Invalid rank at line {}: {e}",
+                        line_no + 1
+                    ))
+                })?;
+                ranks.insert(token_bytes, rank);
+            }
+        }
+
+        let name = name
+            .ok_or_else(|| TiktokenError::DataError("Missing 'name' header".to_string()))?;
+        let pattern = pattern
+            .ok_or_else(|| TiktokenError::DataError("Missing 'pattern' header".to_string()))?;
+
+        Encoding::new(name, ranks, special_tokens, &pattern)
+    }
+
+    /// Dump this encoding's full mergeable-ranks table, special tokens, name,
+    /// and split pattern into a compact, self-describing binary blob (see
+    /// [`crate::binary_format`]), so it can be memory-mapped or embedded and
+    /// reloaded with [`Encoding::from_bytes`] without re-parsing a text
+    /// vocab file.
+    ///
+    /// This is the raw tagged payload with no framing of its own - every
+    /// value is self-describing via its own tag and length prefix, so a
+    /// reader that doesn't understand a field can skip it, but there's no
+    /// magic header or version byte to reject a blob from an unrelated
+    /// format outright. Callers that want that (plus an `impl Read`/`Write`
+    /// interface) should use [`Encoding::serialize_to`]/[`Encoding::load_from`]
+    /// instead, which wrap this payload with exactly that framing.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_tagged_value().to_bytes()
+    }
+
+    /// Reconstruct an `Encoding` previously serialized with
+    /// [`Encoding::to_bytes`]. Rejects a blob whose ranks would produce an
+    /// inconsistent decoder (two different tokens sharing a rank).
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Encoding::from_tagged_value(&TaggedValue::from_bytes(data)?)
+    }
+
+    fn to_tagged_value(&self) -> TaggedValue {
+        let mut special_tokens: Vec<(&String, &Token)> = self.core.special_tokens().iter().collect();
+        special_tokens.sort_by_key(|(_, &rank)| rank);
+        let special_tokens_value = TaggedValue::List(
+            special_tokens
+                .into_iter()
+                .map(|(name, &rank)| {
+                    TaggedValue::Record(vec![
+                        ("name".to_string(), TaggedValue::blob(name.as_bytes().to_vec())),
+                        ("rank".to_string(), TaggedValue::Natural(rank as u64)),
+                    ])
+                })
+                .collect(),
+        );
+
+        let mut ranks: Vec<(&Vec<u8>, &Token)> = self.core.mergeable_ranks().iter().collect();
+        ranks.sort_by_key(|(_, &rank)| rank);
+        let ranks_value = TaggedValue::List(
+            ranks
+                .into_iter()
+                .map(|(bytes, &rank)| {
+                    TaggedValue::Record(vec![
+                        ("token".to_string(), TaggedValue::blob(bytes.clone())),
+                        ("rank".to_string(), TaggedValue::Natural(rank as u64)),
+                    ])
+                })
+                .collect(),
+        );
+
+        TaggedValue::Record(vec![
+            ("name".to_string(), TaggedValue::blob(self.name.as_bytes().to_vec())),
+            ("pattern".to_string(), TaggedValue::blob(self.core.pattern().as_bytes().to_vec())),
+            ("special_tokens".to_string(), special_tokens_value),
+            ("ranks".to_string(), ranks_value),
+        ])
+    }
+
+    fn from_tagged_value(root: &TaggedValue) -> Result<Self> {
+        let field_str = |key: &str| -> Result<String> {
+            root.field(key)
+                .and_then(TaggedValue::as_blob)
+                .ok_or_else(|| TiktokenError::DataError(format!("Missing '{key}' field")))
+                .and_then(|bytes| {
+                    String::from_utf8(bytes.to_vec()).map_err(|e| TiktokenError::DataError(e.to_string()))
+                })
+        };
+
+        let name = field_str("name")?;
+        let pattern = field_str("pattern")?;
+
+        let mut special_tokens = HashMap::new();
+        for item in root.field("special_tokens").and_then(TaggedValue::as_list).unwrap_or(&[]) {
+            let token_name = item
+                .field("name")
+                .and_then(TaggedValue::as_blob)
+                .ok_or_else(|| TiktokenError::DataError("Missing special token name".to_string()))
+                .and_then(|bytes| {
+                    String::from_utf8(bytes.to_vec())
+                        .map_err(|e| TiktokenError::DataError(e.to_string()))
+                })?;
+            let rank = item
+                .field("rank")
+                .and_then(TaggedValue::as_natural)
+                .ok_or_else(|| TiktokenError::DataError("Missing special token rank".to_string()))?
+                as Token;
+            special_tokens.insert(token_name, rank);
+        }
+
+        let mut ranks = HashMap::new();
+        let mut seen_ranks = std::collections::HashSet::new();
+        for item in root.field("ranks").and_then(TaggedValue::as_list).unwrap_or(&[]) {
+            let token = item
+                .field("token")
+                .and_then(TaggedValue::as_blob)
+                .ok_or_else(|| TiktokenError::DataError("Missing rank token".to_string()))?
+                .to_vec();
+            let rank = item
+                .field("rank")
+                .and_then(TaggedValue::as_natural)
+                .ok_or_else(|| TiktokenError::DataError("Missing rank value".to_string()))?
+                as Rank;
+
+            // Two different token byte sequences sharing a rank would make
+            // the rebuilt decoder (rank -> bytes) silently drop one of them,
+            // so a tampered or truncated blob must fail loudly here instead.
+            if !seen_ranks.insert(rank) {
+                return Err(TiktokenError::DataError(format!(
+                    "Duplicate rank {rank} in serialized ranks: decoder would not be consistent"
+                )));
+            }
+
+            ranks.insert(token, rank);
+        }
+
+        Encoding::new(name, ranks, special_tokens, &pattern)
+    }
+
+    /// Magic header identifying a precompiled encoding blob written by
+    /// [`Encoding::serialize_to`], so [`Encoding::load_from`] can reject
+    /// garbage input before attempting to parse it.
+    const BINARY_MAGIC: &[u8; 4] = b"TKE1";
+
+    /// Version of the binary blob layout written by [`Encoding::serialize_to`].
+    /// Bump this if the payload format ever changes incompatibly.
+    const BINARY_VERSION: u8 = 1;
+
+    /// Write this encoding's full rank tables, special tokens, name, and
+    /// split pattern to `writer` as a versioned binary blob, so a later
+    /// [`Encoding::load_from`] can reconstruct it by directly populating the
+    /// rank maps instead of re-parsing a text vocab file. This is the same
+    /// payload [`Encoding::to_bytes`] produces, framed with a magic header
+    /// and version byte for forward compatibility.
+    pub fn serialize_to<W: std::io::Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(Self::BINARY_MAGIC)?;
+        writer.write_all(&[Self::BINARY_VERSION])?;
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Reconstruct an `Encoding` previously written with
+    /// [`Encoding::serialize_to`].
+    pub fn load_from<R: std::io::Read>(mut reader: R) -> Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let header_len = Self::BINARY_MAGIC.len() + 1;
+        if data.len() < header_len || &data[..Self::BINARY_MAGIC.len()] != Self::BINARY_MAGIC.as_slice() {
+            return Err(TiktokenError::DataError(
+                "Binary encoding blob has an invalid or missing magic header".to_string(),
+            ));
+        }
+        let version = data[Self::BINARY_MAGIC.len()];
+        if version != Self::BINARY_VERSION {
+            return Err(TiktokenError::DataError(format!(
+                "Unsupported binary encoding blob version: {version}"
+            )));
+        }
+
+        Encoding::from_bytes(&data[header_len..])
+    }
+
+    /// Count the tokens a list of chat messages will consume for `model`,
+    /// following OpenAI's accounting: each message costs a fixed overhead
+    /// plus the encoded length of its role/content/name, and the whole
+    /// conversation costs 3 extra tokens to prime the assistant's reply.
+    ///
+    /// `gpt-3.5-turbo-0301` used a different overhead (`tokens_per_message =
+    /// 4`, `tokens_per_name = -1`); every later chat model uses `3` and `1`.
+    pub fn count_chat_tokens(&self, messages: &[ChatMessage], model: &str) -> usize {
+        let (tokens_per_message, tokens_per_name): (i64, i64) =
+            if model == "gpt-3.5-turbo-0301" { (4, -1) } else { (3, 1) };
+
+        let mut num_tokens: i64 = 0;
+        for message in messages {
+            num_tokens += tokens_per_message;
+            num_tokens += self.encode_ordinary(&message.role).len() as i64;
+            num_tokens += self.encode_ordinary(&message.content).len() as i64;
+            if let Some(name) = &message.name {
+                num_tokens += self.encode_ordinary(name).len() as i64;
+                num_tokens += tokens_per_name;
+            }
+        }
+        num_tokens += 3; // every reply is primed with <|start|>assistant<|message|>
+
+        num_tokens.max(0) as usize
+    }
+
+    /// How many tokens are left in a `max`-token budget after encoding
+    /// `text`. Negative when `text` already exceeds the budget.
+    pub fn remaining_tokens(&self, text: &str, max: usize) -> isize {
+        max as isize - self.encode_ordinary(text).len() as isize
+    }
+
+    /// Encode `text`, but stop and error as soon as more than `max` tokens
+    /// would be produced, instead of encoding the whole input first.
+    pub fn encode_with_limit(&self, text: &str, max: usize) -> Result<Vec<Token>> {
+        self.core.encode_ordinary_with_limit(text, max)
+    }
+
+    /// Fit `text` into a `max`-token budget: encode, keep the first `max`
+    /// tokens, and decode back to a valid UTF-8 string, trimming any
+    /// trailing token that would split a multi-byte character. Returns the
+    /// truncated text and how many tokens were dropped.
+    pub fn truncate_to_tokens(&self, text: &str, max: usize) -> (String, usize) {
+        let tokens = self.encode_ordinary(text);
+        if tokens.len() <= max {
+            return (text.to_string(), 0);
+        }
+
+        let mut keep = max;
+        loop {
+            match self.decode(&tokens[..keep]) {
+                Ok(truncated) => return (truncated, tokens.len() - keep),
+                Err(_) if keep > 0 => keep -= 1,
+                Err(_) => return (String::new(), tokens.len()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encodings::get_encoding;
+
+    /// Tiny deterministic PRNG so the cross-check below doesn't need an
+    /// external `rand` dependency.
+    struct Xorshift(u64);
+
+    impl Xorshift {
+        fn next_u64(&mut self) -> u64 {
+            let mut x = self.0;
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            self.0 = x;
+            x
+        }
+
+        fn next_byte_string(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| (self.next_u64() % 256) as u8).collect()
+        }
+    }
+
+    #[test]
+    fn test_fancy_regex_fallback_roundtrips() {
+        // cl100k_base's real split pattern uses `\s+(?!\S)`, a negative
+        // lookahead the `regex` crate rejects, so this exercises the
+        // `fancy_regex` fallback path end to end.
+        let enc = get_encoding("cl100k_base").unwrap();
+        let text = "Hello,   world!\n\nTrailing whitespace   ";
+        let tokens = enc.encode_ordinary(text);
+        let decoded = enc.decode(&tokens).unwrap();
+        assert_eq!(decoded, text);
+    }
+
+    #[test]
+    fn test_byte_pair_merge_matches_naive_reference() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let mut rng = Xorshift(0x2545F4914F6CDD1D);
+
+        for _ in 0..200 {
+            let len = 2 + (rng.next_u64() % 20) as usize;
+            let piece = rng.next_byte_string(len);
+
+            let fast = enc.core.byte_pair_merge(&piece);
+            let naive = enc.core.byte_pair_merge_naive(&piece);
+            assert_eq!(fast, naive, "mismatch for piece {piece:?}");
+        }
+    }
+
+    #[test]
+    fn test_count_ordinary_matches_encode_ordinary_len() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let text = "Hello, world! This is a longer sentence with    extra spaces.\n\nAnd a second paragraph.";
+
+        assert_eq!(enc.count_ordinary(text), enc.encode_ordinary(text).len());
+    }
+
+    #[test]
+    fn test_count_matches_encode_len() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let text = "Hello <|endoftext|> world!";
+
+        let expected = enc.encode(text, &["<|endoftext|>"], &[]).unwrap().len();
+        let count = enc.count(text, &["<|endoftext|>"], &[]).unwrap();
+
+        assert_eq!(count, expected);
+    }
 }
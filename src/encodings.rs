@@ -4,14 +4,33 @@ use crate::core::{Encoding, Rank};
 use crate::errors::{Result, TiktokenError};
 use crate::vocab;
 use std::collections::HashMap;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock, RwLock};
 
 /// Type alias for encoding constructor functions
-type EncodingConstructor = fn() -> Result<Encoding>;
+pub type EncodingConstructor = fn() -> Result<Encoding>;
 
 /// Registry of all available encodings
 static ENCODING_REGISTRY: OnceLock<HashMap<String, EncodingConstructor>> = OnceLock::new();
 
+/// Overlay of encodings registered at runtime, consulted before falling back
+/// to `ENCODING_REGISTRY`. Kept separate so the built-in table stays a fixed
+/// `OnceLock` map; every lookup takes this overlay's read lock first.
+static CUSTOM_ENCODINGS: OnceLock<RwLock<HashMap<String, EncodingConstructor>>> = OnceLock::new();
+
+fn custom_encodings() -> &'static RwLock<HashMap<String, EncodingConstructor>> {
+    CUSTOM_ENCODINGS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Process-wide cache of already-built encodings, keyed by name. Constructing
+/// an `Encoding` means loading and hashing its full mergeable-ranks table
+/// (100k-200k entries), so repeated lookups of the same name share one
+/// `Arc<Encoding>` instead of rebuilding it every time.
+static ENCODING_CACHE: OnceLock<RwLock<HashMap<String, Arc<Encoding>>>> = OnceLock::new();
+
+fn encoding_cache() -> &'static RwLock<HashMap<String, Arc<Encoding>>> {
+    ENCODING_CACHE.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
 /// Initialize the encoding registry
 fn init_registry() -> HashMap<String, EncodingConstructor> {
     let mut registry = HashMap::new();
@@ -26,8 +45,35 @@ fn init_registry() -> HashMap<String, EncodingConstructor> {
     registry
 }
 
-/// Get an encoding by name
-pub fn get_encoding(name: &str) -> Result<Encoding> {
+/// Register a custom encoding constructor under `name`, making it resolvable
+/// through [`get_encoding`] and [`get_encoding_arc`]. Registering a name that
+/// matches a built-in encoding overrides it. [`get_encoding_from_any_vendor`]
+/// only resolves names in a vendor's fixed encoding list, so it sees such an
+/// override of a built-in name but not a newly registered name.
+///
+/// [`get_encoding_from_any_vendor`]: crate::get_encoding_from_any_vendor
+pub fn register_encoding(name: &str, constructor: EncodingConstructor) {
+    custom_encodings().write().unwrap().insert(name.to_string(), constructor);
+    encoding_cache().write().unwrap().remove(name);
+}
+
+/// Remove a previously registered custom encoding. Returns `true` if an
+/// entry was present and removed. Built-in encodings cannot be unregistered
+/// this way; unregistering an override simply restores the built-in.
+pub fn unregister_encoding(name: &str) -> bool {
+    let removed = custom_encodings().write().unwrap().remove(name).is_some();
+    encoding_cache().write().unwrap().remove(name);
+    removed
+}
+
+/// Build an encoding by name, consulting the runtime overlay first and
+/// falling back to the built-in registry. Does not consult or populate the
+/// cache; callers that want caching should go through [`get_encoding_arc`].
+fn build_encoding(name: &str) -> Result<Encoding> {
+    if let Some(constructor) = custom_encodings().read().unwrap().get(name) {
+        return constructor();
+    }
+
     let registry = ENCODING_REGISTRY.get_or_init(init_registry);
 
     if let Some(constructor) = registry.get(name) {
@@ -37,10 +83,41 @@ pub fn get_encoding(name: &str) -> Result<Encoding> {
     }
 }
 
-/// List all available encoding names
+/// Get a shared, cached encoding by name. The first call for a given name
+/// builds and memoizes it; subsequent calls return the same `Arc<Encoding>`
+/// without re-parsing the vocabulary.
+pub fn get_encoding_arc(name: &str) -> Result<Arc<Encoding>> {
+    if let Some(cached) = encoding_cache().read().unwrap().get(name) {
+        return Ok(Arc::clone(cached));
+    }
+
+    let encoding = Arc::new(build_encoding(name)?);
+    encoding_cache().write().unwrap().insert(name.to_string(), Arc::clone(&encoding));
+    Ok(encoding)
+}
+
+/// Get an encoding by name.
+///
+/// This is a thin, backward-compatible wrapper around [`get_encoding_arc`]:
+/// it shares the same memoized cache (so repeated calls skip re-parsing the
+/// vocabulary) but clones out of the `Arc` to preserve the owned `Encoding`
+/// return type callers already depend on.
+pub fn get_encoding(name: &str) -> Result<Encoding> {
+    get_encoding_arc(name).map(|encoding| (*encoding).clone())
+}
+
+/// List all available encoding names, including those registered at runtime
 pub fn list_encodings() -> Vec<String> {
     let registry = ENCODING_REGISTRY.get_or_init(init_registry);
-    registry.keys().cloned().collect()
+    let mut names: Vec<String> = registry.keys().cloned().collect();
+
+    for name in custom_encodings().read().unwrap().keys() {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+
+    names
 }
 
 // Special token constants
@@ -50,71 +127,82 @@ const FIM_MIDDLE: &str = "<|fim_middle|>";
 const FIM_SUFFIX: &str = "<|fim_suffix|>";
 const ENDOFPROMPT: &str = "<|endofprompt|>";
 
-// Regex patterns
+// Regex patterns. CL100K_PAT_STR and O200K_PAT_STR are the real upstream
+// tiktoken patterns, which rely on the `\s+(?!\S)` negative lookahead that
+// the `regex` crate can't compile; `CoreBPE` falls back to `fancy_regex` for
+// those automatically (see `SplitRegex` in `core.rs`), giving byte-exact
+// parity with OpenAI's encodings instead of a simplified approximation.
 const R50K_PAT_STR: &str = r"'(?:[sdmt]|ll|ve|re)| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+";
 
-const CL100K_PAT_STR: &str = r"\p{L}+|\p{N}+|[^\s\p{L}\p{N}]+|\s+";
+const CL100K_PAT_STR: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
 
-const O200K_PAT_STR: &str = r"[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]*[\p{Ll}\p{Lm}\p{Lo}\p{M}]+(?i:'s|'t|'re|'ve|'m|'ll|'d)?|[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]+[\p{Ll}\p{Lm}\p{Lo}\p{M}]*(?i:'s|'t|'re|'ve|'m|'ll|'d)?|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n/]*|\s*[\r\n]+|\s+";
+const O200K_PAT_STR: &str = r"[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]*[\p{Ll}\p{Lm}\p{Lo}\p{M}]+(?i:'s|'t|'re|'ve|'m|'ll|'d)?|[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]+[\p{Ll}\p{Lm}\p{Lo}\p{M}]*(?i:'s|'t|'re|'ve|'m|'ll|'d)?|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n/]*|\s*[\r\n]+|\s+(?!\S)|\s+";
 
 /// Create the r50k_base encoding
 pub fn r50k_base() -> Result<Encoding> {
-    let mergeable_ranks = load_r50k_base_ranks()?;
     let mut special_tokens = HashMap::new();
     special_tokens.insert(ENDOFTEXT.to_string(), 50256);
+    let mergeable_ranks = load_r50k_base_ranks(special_tokens.len())?;
 
     Encoding::new("r50k_base".to_string(), mergeable_ranks, special_tokens, R50K_PAT_STR)
 }
 
 /// Create the p50k_base encoding
 pub fn p50k_base() -> Result<Encoding> {
-    let mergeable_ranks = load_p50k_base_ranks()?;
     let mut special_tokens = HashMap::new();
     special_tokens.insert(ENDOFTEXT.to_string(), 50256);
+    let mergeable_ranks = load_p50k_base_ranks(special_tokens.len())?;
 
     Encoding::new("p50k_base".to_string(), mergeable_ranks, special_tokens, R50K_PAT_STR)
 }
 
 /// Create the p50k_edit encoding
 pub fn p50k_edit() -> Result<Encoding> {
-    let mergeable_ranks = load_p50k_base_ranks()?;
     let mut special_tokens = HashMap::new();
     special_tokens.insert(ENDOFTEXT.to_string(), 50256);
     special_tokens.insert(FIM_PREFIX.to_string(), 50281);
     special_tokens.insert(FIM_MIDDLE.to_string(), 50282);
     special_tokens.insert(FIM_SUFFIX.to_string(), 50283);
+    // p50k_edit loads the same mergeable ranks as p50k_base, but the
+    // registered expected_n_vocab is p50k_base's own total (merges + its one
+    // special token), not p50k_edit's 4. Pass p50k_base's special-token count
+    // here so the size check validates the shared ranks file without
+    // misfiring on p50k_edit's extra FIM tokens.
+    let mergeable_ranks = load_p50k_base_ranks(1)?;
 
     Encoding::new("p50k_edit".to_string(), mergeable_ranks, special_tokens, R50K_PAT_STR)
 }
 
 /// Create the cl100k_base encoding
 pub fn cl100k_base() -> Result<Encoding> {
-    let mergeable_ranks = load_cl100k_base_ranks()?;
     let mut special_tokens = HashMap::new();
     special_tokens.insert(ENDOFTEXT.to_string(), 100257);
     special_tokens.insert(FIM_PREFIX.to_string(), 100258);
     special_tokens.insert(FIM_MIDDLE.to_string(), 100259);
     special_tokens.insert(FIM_SUFFIX.to_string(), 100260);
     special_tokens.insert(ENDOFPROMPT.to_string(), 100276);
+    let mergeable_ranks = load_cl100k_base_ranks(special_tokens.len())?;
 
     Encoding::new("cl100k_base".to_string(), mergeable_ranks, special_tokens, CL100K_PAT_STR)
 }
 
 /// Create the o200k_base encoding
 pub fn o200k_base() -> Result<Encoding> {
-    let mergeable_ranks = load_o200k_base_ranks()?;
     let mut special_tokens = HashMap::new();
     special_tokens.insert(ENDOFTEXT.to_string(), 199999);
     special_tokens.insert(ENDOFPROMPT.to_string(), 200018);
+    let mergeable_ranks = load_o200k_base_ranks(special_tokens.len())?;
 
     Encoding::new("o200k_base".to_string(), mergeable_ranks, special_tokens, O200K_PAT_STR)
 }
 
-/// Create the gpt2 encoding (same as r50k_base)
+/// Create the gpt2 encoding, loaded from GPT-2's original data-gym vocabulary
+/// format (`vocab.bpe` + `encoder.json`) rather than r50k_base's `.tiktoken`
+/// file.
 pub fn gpt2() -> Result<Encoding> {
-    let mergeable_ranks = load_r50k_base_ranks()?;
     let mut special_tokens = HashMap::new();
     special_tokens.insert(ENDOFTEXT.to_string(), 50256);
+    let mergeable_ranks = vocab::load_data_gym_bpe("gpt2", special_tokens.len())?;
 
     Encoding::new("gpt2".to_string(), mergeable_ranks, special_tokens, R50K_PAT_STR)
 }
@@ -123,21 +211,21 @@ pub fn gpt2() -> Result<Encoding> {
 // In a real implementation, these would load from embedded data or external files
 
 /// Load r50k_base vocabulary ranks
-fn load_r50k_base_ranks() -> Result<HashMap<Vec<u8>, Rank>> {
-    vocab::load_tiktoken_bpe("r50k_base")
+fn load_r50k_base_ranks(num_special_tokens: usize) -> Result<HashMap<Vec<u8>, Rank>> {
+    vocab::load_tiktoken_bpe("r50k_base", num_special_tokens)
 }
 
 /// Load p50k_base vocabulary ranks
-fn load_p50k_base_ranks() -> Result<HashMap<Vec<u8>, Rank>> {
-    vocab::load_tiktoken_bpe("p50k_base")
+fn load_p50k_base_ranks(num_special_tokens: usize) -> Result<HashMap<Vec<u8>, Rank>> {
+    vocab::load_tiktoken_bpe("p50k_base", num_special_tokens)
 }
 
 /// Load cl100k_base vocabulary ranks
-fn load_cl100k_base_ranks() -> Result<HashMap<Vec<u8>, Rank>> {
-    vocab::load_tiktoken_bpe("cl100k_base")
+fn load_cl100k_base_ranks(num_special_tokens: usize) -> Result<HashMap<Vec<u8>, Rank>> {
+    vocab::load_tiktoken_bpe("cl100k_base", num_special_tokens)
 }
 
 /// Load o200k_base vocabulary ranks
-fn load_o200k_base_ranks() -> Result<HashMap<Vec<u8>, Rank>> {
-    vocab::load_tiktoken_bpe("o200k_base")
+fn load_o200k_base_ranks(num_special_tokens: usize) -> Result<HashMap<Vec<u8>, Rank>> {
+    vocab::load_tiktoken_bpe("o200k_base", num_special_tokens)
 }
@@ -26,6 +26,9 @@ pub enum TiktokenError {
     /// Error when loading encoding data
     DataError(String),
 
+    /// Error from an underlying I/O operation (e.g. a streaming reader/writer)
+    IoError(String),
+
     /// Generic error for other cases
     Other(String),
 }
@@ -54,6 +57,9 @@ impl fmt::Display for TiktokenError {
             TiktokenError::DataError(msg) => {
                 write!(f, "Data error: {msg}")
             }
+            TiktokenError::IoError(msg) => {
+                write!(f, "I/O error: {msg}")
+            }
             TiktokenError::Other(msg) => {
                 write!(f, "Error: {msg}")
             }
@@ -75,5 +81,20 @@ impl From<std::string::FromUtf8Error> for TiktokenError {
     }
 }
 
+impl From<std::io::Error> for TiktokenError {
+    fn from(err: std::io::Error) -> Self {
+        TiktokenError::IoError(err.to_string())
+    }
+}
+
+impl From<TiktokenError> for std::io::Error {
+    fn from(err: TiktokenError) -> Self {
+        match err {
+            TiktokenError::IoError(msg) => std::io::Error::other(msg),
+            other => std::io::Error::other(other.to_string()),
+        }
+    }
+}
+
 /// Convenience type alias for Results in this library
 pub type Result<T> = std::result::Result<T, TiktokenError>;
@@ -28,18 +28,25 @@
 //! let token_count = enc.encode("Hello, world!", &[], &[]).unwrap().len();
 //! ```
 
+mod binary_format;
 pub mod core;
 pub mod encodings;
 pub mod errors;
 pub mod models;
+pub mod streaming;
 pub mod vendors;
 pub mod vocab;
 
 // Re-export main types and functions for convenience
-pub use core::{CoreBPE, Encoding};
-pub use encodings::{get_encoding, list_encodings};
+pub use core::{ChatMessage, CoreBPE, Encoding, FimMode};
+pub use encodings::{
+    get_encoding, get_encoding_arc, list_encodings, register_encoding, unregister_encoding,
+};
 pub use errors::{Result, TiktokenError};
-pub use models::{encoding_for_model, encoding_name_for_model};
+pub use models::{
+    encoding_for_model, encoding_name_for_model, register_model, register_model_prefix,
+};
+pub use streaming::{TokenDecodeWriter, TokenReader};
 pub use vendors::{VendorProvider, VendorRegistry};
 
 /// The main result type used throughout the library
@@ -241,4 +248,215 @@ mod tests {
         let result = get_encoding_for_any_model("unknown-model-12345");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_register_and_unregister_encoding() {
+        fn custom_encoding() -> Result<Encoding> {
+            get_encoding("gpt2")
+        }
+
+        register_encoding("my_custom_vocab", custom_encoding);
+        assert!(list_encodings().contains(&"my_custom_vocab".to_string()));
+        let enc = get_encoding("my_custom_vocab").unwrap();
+        assert_eq!(enc.name, "gpt2");
+
+        assert!(unregister_encoding("my_custom_vocab"));
+        assert!(get_encoding("my_custom_vocab").is_err());
+    }
+
+    #[test]
+    fn test_get_encoding_arc_is_shared() {
+        let a = get_encoding_arc("cl100k_base").unwrap();
+        let b = get_encoding_arc("cl100k_base").unwrap();
+        assert!(std::sync::Arc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn test_encode_fim_psm_and_spm() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let special_tokens = enc.special_tokens();
+        let fim_prefix = special_tokens["<|fim_prefix|>"];
+        let fim_middle = special_tokens["<|fim_middle|>"];
+        let fim_suffix = special_tokens["<|fim_suffix|>"];
+
+        let prefix_tokens = enc.encode_ordinary("def add(a, b):\n    ");
+        let suffix_tokens = enc.encode_ordinary("\n    return a + b");
+
+        let psm = enc.encode_fim("def add(a, b):\n    ", "\n    return a + b", FimMode::Psm).unwrap();
+        let mut expected = vec![fim_prefix];
+        expected.extend(&prefix_tokens);
+        expected.push(fim_suffix);
+        expected.extend(&suffix_tokens);
+        expected.push(fim_middle);
+        assert_eq!(psm, expected);
+
+        let spm = enc.encode_fim("def add(a, b):\n    ", "\n    return a + b", FimMode::Spm).unwrap();
+        let mut expected = vec![fim_prefix, fim_suffix];
+        expected.extend(&suffix_tokens);
+        expected.push(fim_middle);
+        expected.extend(&prefix_tokens);
+        assert_eq!(spm, expected);
+    }
+
+    #[test]
+    fn test_encode_fim_requires_fim_tokens() {
+        let enc = get_encoding("o200k_base").unwrap();
+        assert!(enc.encode_fim("a", "b", FimMode::Psm).is_err());
+    }
+
+    #[test]
+    fn test_definition_text_roundtrip() {
+        let enc = get_encoding("gpt2").unwrap();
+        let text = enc.to_definition_text();
+        let reconstructed = Encoding::from_definition_text(&text).unwrap();
+
+        assert_eq!(reconstructed.name, enc.name);
+        assert_eq!(reconstructed.vocab_size(), enc.vocab_size());
+        assert_eq!(reconstructed.special_tokens(), enc.special_tokens());
+
+        let sample = "Hello, world!";
+        assert_eq!(reconstructed.encode_ordinary(sample), enc.encode_ordinary(sample));
+    }
+
+    #[test]
+    fn test_remaining_tokens() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let text = "Hello, world!";
+        let used = enc.encode_ordinary(text).len();
+
+        assert_eq!(enc.remaining_tokens(text, used + 5), 5);
+        assert_eq!(enc.remaining_tokens(text, used), 0);
+        assert!(enc.remaining_tokens(text, used - 1) < 0);
+    }
+
+    #[test]
+    fn test_truncate_to_tokens() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let tokens = enc.encode_ordinary(text);
+
+        let (truncated, dropped) = enc.truncate_to_tokens(text, tokens.len());
+        assert_eq!(truncated, text);
+        assert_eq!(dropped, 0);
+
+        let (truncated, dropped) = enc.truncate_to_tokens(text, 2);
+        assert_eq!(dropped, tokens.len() - 2);
+        assert_eq!(enc.encode_ordinary(&truncated).len(), 2);
+    }
+
+    #[test]
+    fn test_encode_with_limit() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let full = enc.encode_ordinary(text);
+
+        let limited = enc.encode_with_limit(text, full.len()).unwrap();
+        assert_eq!(limited, full);
+
+        assert!(enc.encode_with_limit(text, 1).is_err());
+    }
+
+    #[test]
+    fn test_to_bytes_from_bytes_roundtrip() {
+        let enc = get_encoding("gpt2").unwrap();
+        let bytes = enc.to_bytes();
+        let reconstructed = Encoding::from_bytes(&bytes).unwrap();
+
+        assert_eq!(reconstructed.name, enc.name);
+        assert_eq!(reconstructed.vocab_size(), enc.vocab_size());
+        assert_eq!(reconstructed.special_tokens(), enc.special_tokens());
+
+        let sample = "Hello, world! 🌍";
+        assert_eq!(reconstructed.encode_ordinary(sample), enc.encode_ordinary(sample));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_duplicate_ranks() {
+        use crate::binary_format::TaggedValue;
+
+        let root = TaggedValue::Record(vec![
+            ("name".to_string(), TaggedValue::blob(b"broken".to_vec())),
+            ("pattern".to_string(), TaggedValue::blob(b".".to_vec())),
+            ("special_tokens".to_string(), TaggedValue::List(vec![])),
+            (
+                "ranks".to_string(),
+                TaggedValue::List(vec![
+                    TaggedValue::Record(vec![
+                        ("token".to_string(), TaggedValue::blob(b"a".to_vec())),
+                        ("rank".to_string(), TaggedValue::Natural(0)),
+                    ]),
+                    TaggedValue::Record(vec![
+                        ("token".to_string(), TaggedValue::blob(b"b".to_vec())),
+                        ("rank".to_string(), TaggedValue::Natural(0)),
+                    ]),
+                ]),
+            ),
+        ]);
+
+        match Encoding::from_bytes(&root.to_bytes()) {
+            Err(TiktokenError::DataError(_)) => {}
+            Err(other) => panic!("expected DataError, got {other:?}"),
+            Ok(_) => panic!("expected an error, got Ok"),
+        }
+    }
+
+    #[test]
+    fn test_serialize_to_load_from_roundtrip() {
+        let enc = get_encoding("gpt2").unwrap();
+
+        let mut blob = Vec::new();
+        enc.serialize_to(&mut blob).unwrap();
+        let reconstructed = Encoding::load_from(blob.as_slice()).unwrap();
+
+        assert_eq!(reconstructed.name, enc.name);
+        assert_eq!(reconstructed.vocab_size(), enc.vocab_size());
+        assert_eq!(reconstructed.special_tokens(), enc.special_tokens());
+
+        let sample = "Hello, world! 🌍";
+        assert_eq!(reconstructed.encode_ordinary(sample), enc.encode_ordinary(sample));
+    }
+
+    #[test]
+    fn test_load_from_rejects_bad_magic() {
+        match Encoding::load_from(&b"NOPE1garbage"[..]) {
+            Err(TiktokenError::DataError(_)) => {}
+            Err(other) => panic!("expected DataError, got {other:?}"),
+            Ok(_) => panic!("expected an error, got Ok"),
+        }
+    }
+
+    #[test]
+    fn test_encode_ordinary_into_appends() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let mut buf = vec![1, 2, 3];
+        enc.encode_ordinary_into("hello world", &mut buf);
+
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+        assert_eq!(&buf[3..], enc.encode_ordinary("hello world").as_slice());
+    }
+
+    #[test]
+    fn test_encode_into_appends() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let mut buf = vec![42];
+        enc.encode_into("hello <|endoftext|> world", &["<|endoftext|>"], &[], &mut buf).unwrap();
+
+        assert_eq!(buf[0], 42);
+        assert_eq!(
+            &buf[1..],
+            enc.encode("hello <|endoftext|> world", &["<|endoftext|>"], &[]).unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn test_decode_into_appends() {
+        let enc = get_encoding("cl100k_base").unwrap();
+        let tokens = enc.encode_ordinary("hello world");
+
+        let mut buf = b"prefix-".to_vec();
+        enc.decode_into(&tokens, &mut buf).unwrap();
+
+        assert_eq!(&buf[..7], b"prefix-");
+        assert_eq!(&buf[7..], enc.decode_bytes(&tokens).unwrap().as_slice());
+    }
 }
@@ -1,10 +1,10 @@
 //! Model to encoding mappings
 
-use crate::core::Encoding;
+use crate::core::{Encoding, Rank};
 use crate::encodings::get_encoding;
 use crate::errors::{Result, TiktokenError};
 use std::collections::HashMap;
-use std::sync::OnceLock;
+use std::sync::{OnceLock, RwLock};
 
 /// Registry of model name prefixes to encoding names
 static MODEL_PREFIX_REGISTRY: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
@@ -12,6 +12,39 @@ static MODEL_PREFIX_REGISTRY: OnceLock<HashMap<&'static str, &'static str>> = On
 /// Registry of exact model names to encoding names
 static MODEL_REGISTRY: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
 
+/// Overlay of exact model names registered at runtime, consulted before
+/// `MODEL_REGISTRY`. Lets callers teach tiktoken about fine-tunes, Azure
+/// deployment names, or other custom model names without forking the crate.
+static CUSTOM_MODEL_REGISTRY: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+
+/// Overlay of model name prefixes registered at runtime, consulted before
+/// `MODEL_PREFIX_REGISTRY`.
+static CUSTOM_MODEL_PREFIX_REGISTRY: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+
+fn custom_model_registry() -> &'static RwLock<HashMap<String, String>> {
+    CUSTOM_MODEL_REGISTRY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+fn custom_model_prefix_registry() -> &'static RwLock<HashMap<String, String>> {
+    CUSTOM_MODEL_PREFIX_REGISTRY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Register an exact model name (e.g. a fine-tune ID or Azure deployment
+/// name) as using `encoding_name`. Overrides any built-in mapping for the
+/// same name.
+pub fn register_model(name: &str, encoding_name: &str) {
+    custom_model_registry().write().unwrap().insert(name.to_string(), encoding_name.to_string());
+}
+
+/// Register a model name prefix (e.g. `"ft:my-org:"`) as using
+/// `encoding_name`. Overrides any built-in mapping for the same prefix.
+pub fn register_model_prefix(prefix: &str, encoding_name: &str) {
+    custom_model_prefix_registry()
+        .write()
+        .unwrap()
+        .insert(prefix.to_string(), encoding_name.to_string());
+}
+
 /// Initialize the model prefix registry
 fn init_prefix_registry() -> HashMap<&'static str, &'static str> {
     let mut registry = HashMap::new();
@@ -113,17 +146,57 @@ fn init_model_registry() -> HashMap<&'static str, &'static str> {
     registry
 }
 
+/// Registry of special tokens for each encoding, keyed by encoding name.
+/// Mirrors the special-token tables baked into each encoding constructor in
+/// `encodings.rs`; kept here too so callers that only need a model's special
+/// token ranks (e.g. to find the end-of-text token) don't have to build a
+/// full `Encoding` first.
+pub fn special_tokens_for_encoding(encoding: &str) -> Option<HashMap<&'static str, Rank>> {
+    match encoding {
+        "r50k_base" | "p50k_base" | "gpt2" => {
+            Some(HashMap::from([("<|endoftext|>", 50256)]))
+        }
+        "p50k_edit" => Some(HashMap::from([
+            ("<|endoftext|>", 50256),
+            ("<|fim_prefix|>", 50281),
+            ("<|fim_middle|>", 50282),
+            ("<|fim_suffix|>", 50283),
+        ])),
+        "cl100k_base" => Some(HashMap::from([
+            ("<|endoftext|>", 100257),
+            ("<|fim_prefix|>", 100258),
+            ("<|fim_middle|>", 100259),
+            ("<|fim_suffix|>", 100260),
+            ("<|endofprompt|>", 100276),
+        ])),
+        "o200k_base" => Some(HashMap::from([
+            ("<|endoftext|>", 199999),
+            ("<|endofprompt|>", 200018),
+        ])),
+        _ => None,
+    }
+}
+
 /// Get the encoding name for a model
 pub fn encoding_name_for_model(model_name: &str) -> Result<String> {
-    let model_registry = MODEL_REGISTRY.get_or_init(init_model_registry);
-    let prefix_registry = MODEL_PREFIX_REGISTRY.get_or_init(init_prefix_registry);
+    // Runtime-registered exact matches take priority over the built-ins.
+    if let Some(encoding_name) = custom_model_registry().read().unwrap().get(model_name) {
+        return Ok(encoding_name.clone());
+    }
 
-    // First check exact matches
+    let model_registry = MODEL_REGISTRY.get_or_init(init_model_registry);
     if let Some(&encoding_name) = model_registry.get(model_name) {
         return Ok(encoding_name.to_string());
     }
 
-    // Then check prefix matches
+    // Then runtime-registered prefixes, then the built-in prefix table.
+    for (prefix, encoding_name) in custom_model_prefix_registry().read().unwrap().iter() {
+        if model_name.starts_with(prefix.as_str()) {
+            return Ok(encoding_name.clone());
+        }
+    }
+
+    let prefix_registry = MODEL_PREFIX_REGISTRY.get_or_init(init_prefix_registry);
     for (&prefix, &encoding_name) in prefix_registry.iter() {
         if model_name.starts_with(prefix) {
             return Ok(encoding_name.to_string());
@@ -136,13 +209,39 @@ pub fn encoding_name_for_model(model_name: &str) -> Result<String> {
 /// Get the encoding for a model
 pub fn encoding_for_model(model_name: &str) -> Result<Encoding> {
     let encoding_name = encoding_name_for_model(model_name)?;
-    get_encoding(&encoding_name)
+    let encoding = get_encoding(&encoding_name)?;
+
+    // Cross-check against the special-token table: a mismatch here means the
+    // encoding constructor and this registry have drifted apart, which would
+    // otherwise surface later as a confusing wrong-token-id bug.
+    if let Some(expected) = special_tokens_for_encoding(&encoding_name) {
+        for (name, rank) in expected {
+            match encoding.special_tokens().get(name) {
+                Some(&actual) if actual == rank => {}
+                _ => {
+                    return Err(TiktokenError::DataError(format!(
+                        "Encoding '{encoding_name}' is missing or has a mismatched special token '{name}'"
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(encoding)
 }
 
-/// List all supported model names
+/// List all supported model names, including those registered at runtime
 pub fn list_supported_models() -> Vec<String> {
     let model_registry = MODEL_REGISTRY.get_or_init(init_model_registry);
-    model_registry.keys().map(|&s| s.to_string()).collect()
+    let mut names: Vec<String> = model_registry.keys().map(|&s| s.to_string()).collect();
+
+    for name in custom_model_registry().read().unwrap().keys() {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+
+    names
 }
 
 /// Check if a model is supported
@@ -179,4 +278,38 @@ mod tests {
         let encoding = encoding_for_model("gpt-4").unwrap();
         assert_eq!(encoding.name, "cl100k_base");
     }
+
+    #[test]
+    fn test_special_tokens_for_encoding() {
+        let cl100k = special_tokens_for_encoding("cl100k_base").unwrap();
+        assert_eq!(cl100k.get("<|endoftext|>"), Some(&100257));
+        assert_eq!(cl100k.get("<|fim_suffix|>"), Some(&100260));
+
+        let o200k = special_tokens_for_encoding("o200k_base").unwrap();
+        assert_eq!(o200k.get("<|endoftext|>"), Some(&199999));
+        assert_eq!(o200k.get("<|endofprompt|>"), Some(&200018));
+
+        assert!(special_tokens_for_encoding("unknown").is_none());
+    }
+
+    #[test]
+    fn test_register_model_exact() {
+        register_model("my-org:ft-widget-001", "cl100k_base");
+        assert_eq!(encoding_name_for_model("my-org:ft-widget-001").unwrap(), "cl100k_base");
+        assert!(list_supported_models().contains(&"my-org:ft-widget-001".to_string()));
+    }
+
+    #[test]
+    fn test_register_model_prefix() {
+        register_model_prefix("azure-my-deployment-", "o200k_base");
+        assert_eq!(encoding_name_for_model("azure-my-deployment-2024-08-01").unwrap(), "o200k_base");
+    }
+
+    #[test]
+    fn test_register_model_overrides_builtin() {
+        // Use a built-in name not asserted by other tests in this module, so
+        // concurrent test runs can't observe a stale override.
+        register_model("davinci", "o200k_base");
+        assert_eq!(encoding_name_for_model("davinci").unwrap(), "o200k_base");
+    }
 }
@@ -0,0 +1,232 @@
+//! Streaming tokenization over `std::io::Read`/`std::io::Write`, for
+//! encoding/decoding text that doesn't fit (or shouldn't be fully buffered)
+//! in memory at once.
+
+use crate::core::{Encoding, Token};
+use crate::errors::{Result, TiktokenError};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+/// Reads tokens incrementally from an `R: Read` of UTF-8 text.
+///
+/// Bytes are buffered only until a regex-split piece boundary is confirmed:
+/// once a new piece has started, every piece before it is final and gets
+/// BPE-encoded and queued, and the consumed bytes are dropped from the
+/// buffer. The one piece still being matched against incoming bytes is kept
+/// back until either another piece starts after it or the stream ends, so
+/// tokenizing in arbitrary chunk sizes always produces the same tokens as
+/// calling [`Encoding::encode_ordinary`] on the whole text at once.
+pub struct TokenReader<R: Read> {
+    reader: R,
+    encoding: Arc<Encoding>,
+    raw_buf: Vec<u8>,
+    pending_tokens: VecDeque<Token>,
+    eof: bool,
+}
+
+impl<R: Read> TokenReader<R> {
+    /// Create a token reader over `reader`, tokenizing with `encoding`.
+    pub fn new(reader: R, encoding: Arc<Encoding>) -> Self {
+        TokenReader { reader, encoding, raw_buf: Vec::new(), pending_tokens: VecDeque::new(), eof: false }
+    }
+
+    fn fill_buf(&mut self) -> Result<()> {
+        let mut chunk = [0u8; 8192];
+        let n = self.reader.read(&mut chunk)?;
+        if n == 0 {
+            self.eof = true;
+        } else {
+            self.raw_buf.extend_from_slice(&chunk[..n]);
+        }
+        Ok(())
+    }
+
+    /// Encode every piece in `raw_buf` that's confirmed complete, queueing
+    /// their tokens and dropping the consumed bytes.
+    fn tokenize_ready_pieces(&mut self) -> Result<()> {
+        let valid_len = match std::str::from_utf8(&self.raw_buf) {
+            Ok(s) => s.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        if valid_len == 0 {
+            return Ok(());
+        }
+        let text = std::str::from_utf8(&self.raw_buf[..valid_len]).expect("validated above");
+
+        let piece_starts = self.encoding.piece_starts(text);
+        let confirmed_end = if self.eof {
+            valid_len
+        } else if piece_starts.len() > 1 {
+            piece_starts[piece_starts.len() - 1]
+        } else {
+            0 // Only (at most) one piece found so far - it may still be growing.
+        };
+
+        if confirmed_end == 0 {
+            return Ok(());
+        }
+
+        let mut tokens = Vec::new();
+        self.encoding.encode_ordinary_into(&text[..confirmed_end], &mut tokens);
+        self.pending_tokens.extend(tokens);
+        self.raw_buf.drain(..confirmed_end);
+        Ok(())
+    }
+
+    /// Pull the next token, reading from the underlying `Read` as needed.
+    /// Returns `Ok(None)` once the stream is exhausted.
+    pub fn next_token(&mut self) -> Result<Option<Token>> {
+        loop {
+            if let Some(token) = self.pending_tokens.pop_front() {
+                return Ok(Some(token));
+            }
+
+            if self.eof {
+                if self.raw_buf.is_empty() {
+                    return Ok(None);
+                }
+                self.tokenize_ready_pieces()?;
+                if self.pending_tokens.is_empty() && !self.raw_buf.is_empty() {
+                    return Err(TiktokenError::DecodingError(
+                        "Token stream ended with an incomplete UTF-8 sequence".to_string(),
+                    ));
+                }
+                continue;
+            }
+
+            self.fill_buf()?;
+            self.tokenize_ready_pieces()?;
+        }
+    }
+}
+
+impl<R: Read> Iterator for TokenReader<R> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Ok(Some(token)) => Some(Ok(token)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Writes decoded UTF-8 text to a `W: Write` as tokens are pushed in.
+///
+/// Token byte sequences aren't guaranteed to end on a `char` boundary, so
+/// decoded bytes are buffered until they form a valid UTF-8 prefix before
+/// being written out; [`TokenDecodeWriter::finish`] flushes (and validates)
+/// whatever's left at the end of the stream.
+pub struct TokenDecodeWriter<W: Write> {
+    writer: W,
+    encoding: Arc<Encoding>,
+    pending_bytes: Vec<u8>,
+}
+
+impl<W: Write> TokenDecodeWriter<W> {
+    /// Create a token decode writer over `writer`, decoding with `encoding`.
+    pub fn new(writer: W, encoding: Arc<Encoding>) -> Self {
+        TokenDecodeWriter { writer, encoding, pending_bytes: Vec::new() }
+    }
+
+    /// Push a single token, writing out any newly-complete decoded text.
+    pub fn push_token(&mut self, token: Token) -> Result<()> {
+        self.push_tokens(&[token])
+    }
+
+    /// Push a batch of tokens, writing out any newly-complete decoded text.
+    pub fn push_tokens(&mut self, tokens: &[Token]) -> Result<()> {
+        self.encoding.decode_into(tokens, &mut self.pending_bytes)?;
+        self.flush_complete_utf8()
+    }
+
+    fn flush_complete_utf8(&mut self) -> Result<()> {
+        let valid_len = match std::str::from_utf8(&self.pending_bytes) {
+            Ok(s) => s.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        if valid_len > 0 {
+            self.writer.write_all(&self.pending_bytes[..valid_len])?;
+            self.pending_bytes.drain(..valid_len);
+        }
+        Ok(())
+    }
+
+    /// Flush any remaining buffered bytes and return the underlying writer.
+    /// Errors if what's left doesn't form valid UTF-8, meaning the token
+    /// stream was truncated mid-character.
+    pub fn finish(mut self) -> Result<W> {
+        if !self.pending_bytes.is_empty() {
+            let text = String::from_utf8(std::mem::take(&mut self.pending_bytes))?;
+            self.writer.write_all(text.as_bytes())?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encodings::get_encoding_arc;
+
+    #[test]
+    fn test_token_reader_matches_encode_ordinary() {
+        let encoding = get_encoding_arc("cl100k_base").unwrap();
+        let text = "Hello, world! This is a streaming test with  multiple   spaces.";
+
+        let expected = encoding.encode_ordinary(text);
+
+        let mut reader = TokenReader::new(text.as_bytes(), Arc::clone(&encoding));
+        let mut actual = Vec::new();
+        while let Some(token) = reader.next_token().unwrap() {
+            actual.push(token);
+        }
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_token_reader_arbitrary_chunk_sizes_match_whole_string() {
+        let encoding = get_encoding_arc("cl100k_base").unwrap();
+        let text = "The quick brown fox jumps over the lazy dog, again and again.";
+        let expected = encoding.encode_ordinary(text);
+
+        struct OneByteAtATime<'a>(&'a [u8]);
+        impl<'a> Read for OneByteAtATime<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() || buf.is_empty() {
+                    return Ok(0);
+                }
+                buf[0] = self.0[0];
+                self.0 = &self.0[1..];
+                Ok(1)
+            }
+        }
+
+        let mut reader = TokenReader::new(OneByteAtATime(text.as_bytes()), encoding);
+        let mut actual = Vec::new();
+        while let Some(token) = reader.next_token().unwrap() {
+            actual.push(token);
+        }
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_token_decode_writer_roundtrips() {
+        let encoding = get_encoding_arc("cl100k_base").unwrap();
+        let text = "Roundtrip through the decode writer, 🎉 included.";
+        let tokens = encoding.encode_ordinary(text);
+
+        let mut writer = TokenDecodeWriter::new(Vec::new(), Arc::clone(&encoding));
+        for &token in &tokens {
+            writer.push_token(token).unwrap();
+        }
+        let out = writer.finish().unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), text);
+    }
+}
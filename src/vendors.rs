@@ -1,6 +1,6 @@
 //! Vendor-specific implementations for different AI providers
 
-use crate::core::Encoding;
+use crate::core::{ChatMessage, Encoding};
 use crate::errors::{Result, TiktokenError};
 use crate::vocab;
 use std::collections::HashMap;
@@ -98,6 +98,16 @@ impl VendorProvider for OpenAIProvider {
     }
 }
 
+impl OpenAIProvider {
+    /// Count the tokens a list of chat messages will consume for `model`,
+    /// resolving `model` to its encoding and delegating to
+    /// [`Encoding::count_chat_tokens`].
+    pub fn count_chat_tokens(&self, messages: &[ChatMessage], model: &str) -> Result<usize> {
+        let encoding = crate::models::encoding_for_model(model)?;
+        Ok(encoding.count_chat_tokens(messages, model))
+    }
+}
+
 /// Anthropic vendor implementation (placeholder for future support)
 pub struct AnthropicProvider;
 
@@ -306,4 +316,24 @@ mod tests {
         let encoding = provider.create_encoding("cl100k_base").unwrap();
         assert_eq!(encoding.name, "cl100k_base");
     }
+
+    #[test]
+    fn test_count_chat_tokens() {
+        let provider = OpenAIProvider;
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: "You are a helpful assistant.".to_string(),
+                name: None,
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: "Hello!".to_string(),
+                name: Some("alice".to_string()),
+            },
+        ];
+
+        let count = provider.count_chat_tokens(&messages, "gpt-4").unwrap();
+        assert!(count > 0);
+    }
 }
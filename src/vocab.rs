@@ -10,6 +10,12 @@ use std::collections::HashMap;
 pub struct VocabInfo {
     pub url: &'static str,
     pub expected_hash: &'static str,
+    /// Total vocabulary size (mergeable ranks + special tokens) this
+    /// encoding is known to have, if known. When set, [`load_tiktoken_bpe`]
+    /// rejects a loaded file whose merge count plus the caller's special
+    /// token count don't add up to it, catching a truncated download or a
+    /// vocabulary swapped in from the wrong encoding.
+    pub expected_n_vocab: Option<usize>,
 }
 
 /// Registry of vocabulary files for different encodings
@@ -18,59 +24,521 @@ pub fn get_vocab_info(encoding: &str) -> Option<VocabInfo> {
         "r50k_base" => Some(VocabInfo {
             url: "https://openaipublic.blob.core.windows.net/encodings/r50k_base.tiktoken",
             expected_hash: "306cd27f03c1a714eca7108e03d66b7dc042abe8c258b44c199a7ed9838dd930",
+            expected_n_vocab: Some(50257),
         }),
         "p50k_base" => Some(VocabInfo {
             url: "https://openaipublic.blob.core.windows.net/encodings/p50k_base.tiktoken",
             expected_hash: "94b5ca7dff4d00767bc256fdd1b27e5b17361d7b8a5f968547f9f23eb70d2069",
+            expected_n_vocab: Some(50281),
         }),
         "cl100k_base" => Some(VocabInfo {
             url: "https://openaipublic.blob.core.windows.net/encodings/cl100k_base.tiktoken",
             expected_hash: "223921b76ee99bde995b7ff738513eef100fb51d18c93597a113bcffe865b2a7",
+            // Upstream tiktoken doesn't set `explicit_n_vocab` for this
+            // encoding either: cl100k_base's highest token id leaves gaps
+            // above the last mergeable rank before the special-token block,
+            // so merges + specials never equals the nominal 100277 n_vocab.
+            expected_n_vocab: None,
         }),
         "o200k_base" => Some(VocabInfo {
             url: "https://openaipublic.blob.core.windows.net/encodings/o200k_base.tiktoken",
             expected_hash: "446a9538cb6c348e3516120d7c08b09f57c36495e2acfffe59a5bf8b0cfb1a2d",
+            // Same reasoning as cl100k_base above: upstream leaves this
+            // unset, since o200k_base's merges + specials don't add up to
+            // the nominal 200019 n_vocab either.
+            expected_n_vocab: None,
         }),
         _ => None,
     }
 }
 
-/// Load tiktoken BPE vocabulary from a URL or embedded data
+/// Check that `ranks.len() + num_special_tokens` matches `expected_n_vocab`,
+/// if one is given. A `None` expectation (e.g. for encodings without a known
+/// published vocab size) skips the check entirely.
+fn validate_vocab_size(
+    ranks: &HashMap<Vec<u8>, Rank>,
+    num_special_tokens: usize,
+    expected_n_vocab: Option<usize>,
+) -> Result<()> {
+    if let Some(expected) = expected_n_vocab {
+        let actual = ranks.len() + num_special_tokens;
+        if actual != expected {
+            return Err(TiktokenError::DataError(format!(
+                "Vocabulary size mismatch: expected {expected} tokens ({} mergeable ranks + {num_special_tokens} special tokens), got {actual}",
+                expected.saturating_sub(num_special_tokens)
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Vocabulary file information for GPT-2's two-file "data-gym" format: a
+/// `vocab.bpe` merge list plus an `encoder.json` token-to-rank table, as
+/// opposed to the single `.tiktoken` rank file the other encodings use.
+#[derive(Debug, Clone)]
+pub struct DataGymVocabInfo {
+    pub vocab_bpe_url: &'static str,
+    pub vocab_bpe_hash: &'static str,
+    pub encoder_json_url: &'static str,
+    pub encoder_json_hash: &'static str,
+}
+
+/// Registry of data-gym vocabulary files for encodings that ship in that
+/// format.
+pub fn get_data_gym_vocab_info(encoding: &str) -> Option<DataGymVocabInfo> {
+    match encoding {
+        "gpt2" => Some(DataGymVocabInfo {
+            vocab_bpe_url: "https://openaipublic.blob.core.windows.net/gpt-2/encodings/main/vocab.bpe",
+            vocab_bpe_hash: "1ce1664773c50f3e0cc8842619a93edc4624525b728b188a9e0be33b7726adc5",
+            encoder_json_url: "https://openaipublic.blob.core.windows.net/gpt-2/encodings/main/encoder.json",
+            encoder_json_hash: "196139668be63f3b5d6574427317ae82f612a97c5d1cdaf36ed2256dbf636783",
+        }),
+        _ => None,
+    }
+}
+
+/// The printable-byte ranges data-gym formats keep literal in `vocab.bpe`/
+/// `encoder.json`: already ascending and non-overlapping, so concatenating
+/// them is the same as sorting their union.
+fn data_gym_printable_bytes() -> Vec<u8> {
+    let mut printable: Vec<u8> = Vec::new();
+    printable.extend(0x21u8..=0x7e);
+    printable.extend(0xa1u8..=0xac);
+    printable.extend(0xaeu8..=0xff);
+    printable
+}
+
+/// The byte order GPT-2's data-gym format assigns single-byte ranks in:
+/// printable bytes first, then every remaining byte in ascending order.
+/// Rank `i` is the single-byte token for `order[i]`, matching upstream's
+/// `data_gym_to_mergeable_bpe_ranks`.
+fn data_gym_rank_order() -> Vec<u8> {
+    let printable = data_gym_printable_bytes();
+    let mut order = printable.clone();
+    for b in 0..=255u8 {
+        if !printable.contains(&b) {
+            order.push(b);
+        }
+    }
+    order
+}
+
+/// Build the reversible byte<->char map data-gym vocabularies use to keep
+/// every byte printable in `vocab.bpe`/`encoder.json`: bytes that are
+/// already printable map to the char with the same codepoint, and every
+/// other byte is assigned a char at codepoint `256 + k`.
+fn data_gym_byte_char_maps() -> (HashMap<u8, char>, HashMap<char, u8>) {
+    let printable = data_gym_printable_bytes();
+
+    let mut byte_to_char = HashMap::new();
+    for &b in &printable {
+        byte_to_char.insert(b, b as char);
+    }
+
+    let mut extra = 0u32;
+    for b in 0..=255u8 {
+        if !printable.contains(&b) {
+            byte_to_char.insert(b, char::from_u32(256 + extra).expect("valid codepoint"));
+            extra += 1;
+        }
+    }
+
+    let char_to_byte: HashMap<char, u8> = byte_to_char.iter().map(|(&b, &c)| (c, b)).collect();
+    (byte_to_char, char_to_byte)
+}
+
+/// Decode a data-gym-encoded string (one char per original byte) back into
+/// raw bytes.
+fn decode_data_gym(value: &str, char_to_byte: &HashMap<char, u8>) -> Result<Vec<u8>> {
+    value
+        .chars()
+        .map(|c| {
+            char_to_byte.get(&c).copied().ok_or_else(|| {
+                TiktokenError::DataError(format!("Char '{c}' is not a valid data-gym byte"))
+            })
+        })
+        .collect()
+}
+
+/// Parse GPT-2's data-gym vocabulary format: `vocab_bpe` (the `vocab.bpe`
+/// merge list) and `encoder_json` (the `encoder.json` token->rank table)
+/// into a `bytes -> rank` map, the same shape `parse_tiktoken_bpe` produces
+/// for the newer single-file encodings.
+///
+/// Ranks are assigned purely from `vocab_bpe`'s merge order (256 single-byte
+/// tokens first, then one rank per merge line in file order); `encoder_json`
+/// is used only to sanity-check that result, since tiktoken's BPE algorithm
+/// assumes ranks are ordered by merge priority and a mismatch there would
+/// silently produce a different tokenizer than the one GPT-2 was trained
+/// with.
+pub fn parse_data_gym_bpe(vocab_bpe: &str, encoder_json: &str) -> Result<HashMap<Vec<u8>, Rank>> {
+    let (_byte_to_char, char_to_byte) = data_gym_byte_char_maps();
+
+    let mut ranks: HashMap<Vec<u8>, Rank> = HashMap::new();
+    for (rank, &b) in data_gym_rank_order().iter().enumerate() {
+        ranks.insert(vec![b], rank as Rank);
+    }
+
+    let mut next_rank: Rank = 256;
+    let mut lines = vocab_bpe.lines();
+    lines.next(); // skip the version-comment header line
+
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let [first, second] = parts[..] else {
+            return Err(TiktokenError::DataError(format!("Invalid vocab.bpe merge line: {line}")));
+        };
+
+        let mut merged = decode_data_gym(first, &char_to_byte)?;
+        merged.extend(decode_data_gym(second, &char_to_byte)?);
+
+        ranks.insert(merged, next_rank);
+        next_rank += 1;
+    }
+
+    verify_data_gym_ranks_against_encoder(&ranks, encoder_json, &char_to_byte)?;
+
+    Ok(ranks)
+}
+
+/// Check that every data-gym-decodable key in `encoder_json` maps to the
+/// same rank we derived from `vocab_bpe`. The special tokens `<|endoftext|>`
+/// and `<|startoftext|>` are not mergeable BPE tokens and are skipped, as
+/// are keys that don't decode through the byte<->char map.
+fn verify_data_gym_ranks_against_encoder(
+    ranks: &HashMap<Vec<u8>, Rank>,
+    encoder_json: &str,
+    char_to_byte: &HashMap<char, u8>,
+) -> Result<()> {
+    let encoder = parse_flat_json_string_int_map(encoder_json)?;
+
+    for (token, &rank) in &encoder {
+        if token == "<|endoftext|>" || token == "<|startoftext|>" {
+            continue;
+        }
+        let Ok(bytes) = decode_data_gym(token, char_to_byte) else {
+            continue;
+        };
+
+        match ranks.get(&bytes) {
+            Some(&expected_rank) if expected_rank as i64 == rank => {}
+            Some(&expected_rank) => {
+                return Err(TiktokenError::DataError(format!(
+                    "encoder.json rank mismatch for token {token:?}: vocab.bpe gives {expected_rank}, encoder.json gives {rank}"
+                )));
+            }
+            None => {
+                return Err(TiktokenError::DataError(format!(
+                    "Token {token:?} present in encoder.json but missing from vocab.bpe merges"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Minimal parser for a flat JSON object of `"string": integer` entries,
+/// just enough to read GPT-2's `encoder.json` without a full JSON dependency.
+fn parse_flat_json_string_int_map(json: &str) -> Result<HashMap<String, i64>> {
+    let chars: Vec<char> = json.chars().collect();
+    let mut i = 0usize;
+    let mut map = HashMap::new();
+
+    fn skip_ws(chars: &[char], i: &mut usize) {
+        while matches!(chars.get(*i), Some(c) if c.is_whitespace()) {
+            *i += 1;
+        }
+    }
+    fn parse_string(chars: &[char], i: &mut usize) -> Result<String> {
+        if chars.get(*i) != Some(&'"') {
+            return Err(TiktokenError::DataError("Expected string in encoder.json".to_string()));
+        }
+        *i += 1;
+        let mut out = String::new();
+        loop {
+            match chars.get(*i) {
+                Some('"') => {
+                    *i += 1;
+                    return Ok(out);
+                }
+                Some('\\') => {
+                    *i += 1;
+                    match chars.get(*i) {
+                        Some('u') => {
+                            *i += 1;
+                            let hex: String = chars.get(*i..*i + 4).map(|s| s.iter().collect()).ok_or_else(
+                                || TiktokenError::DataError("Truncated \\u escape".to_string()),
+                            )?;
+                            let code = u32::from_str_radix(&hex, 16)
+                                .map_err(|e| TiktokenError::DataError(e.to_string()))?;
+                            out.push(char::from_u32(code).ok_or_else(|| {
+                                TiktokenError::DataError("Invalid \\u escape".to_string())
+                            })?);
+                            *i += 4;
+                        }
+                        Some('n') => {
+                            out.push('\n');
+                            *i += 1;
+                        }
+                        Some('t') => {
+                            out.push('\t');
+                            *i += 1;
+                        }
+                        Some('r') => {
+                            out.push('\r');
+                            *i += 1;
+                        }
+                        Some(&c @ ('"' | '\\' | '/')) => {
+                            out.push(c);
+                            *i += 1;
+                        }
+                        _ => {
+                            return Err(TiktokenError::DataError(
+                                "Unsupported escape in encoder.json".to_string(),
+                            ));
+                        }
+                    }
+                }
+                Some(&c) => {
+                    out.push(c);
+                    *i += 1;
+                }
+                None => {
+                    return Err(TiktokenError::DataError("Unterminated string in encoder.json".to_string()));
+                }
+            }
+        }
+    }
+
+    skip_ws(&chars, &mut i);
+    if chars.get(i) != Some(&'{') {
+        return Err(TiktokenError::DataError("Expected '{' at start of encoder.json".to_string()));
+    }
+    i += 1;
+    skip_ws(&chars, &mut i);
+    if chars.get(i) == Some(&'}') {
+        return Ok(map);
+    }
+
+    loop {
+        skip_ws(&chars, &mut i);
+        let key = parse_string(&chars, &mut i)?;
+        skip_ws(&chars, &mut i);
+        if chars.get(i) != Some(&':') {
+            return Err(TiktokenError::DataError("Expected ':' in encoder.json".to_string()));
+        }
+        i += 1;
+        skip_ws(&chars, &mut i);
+
+        let start = i;
+        while !matches!(chars.get(i), Some(',') | Some('}') | None) {
+            i += 1;
+        }
+        let value_str: String = chars[start..i].iter().collect();
+        let value: i64 = value_str
+            .trim()
+            .parse()
+            .map_err(|e: std::num::ParseIntError| TiktokenError::DataError(e.to_string()))?;
+        map.insert(key, value);
+
+        skip_ws(&chars, &mut i);
+        match chars.get(i) {
+            Some(',') => {
+                i += 1;
+            }
+            Some('}') => {
+                break;
+            }
+            _ => return Err(TiktokenError::DataError("Malformed encoder.json".to_string())),
+        }
+    }
+
+    Ok(map)
+}
+
+/// Directory the on-disk vocabulary cache writes into: `$TIKTOKEN_CACHE_DIR`
+/// if set, otherwise a `tiktoken` subdirectory of the OS temp dir. Mirrors
+/// upstream tiktoken's cache location convention so the two can share a
+/// cache directory.
+#[cfg(feature = "download")]
+fn cache_dir() -> std::path::PathBuf {
+    std::env::var_os("TIKTOKEN_CACHE_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("tiktoken"))
+}
+
+/// Fetch `url`, verifying its content against `expected_hash`, using an
+/// on-disk cache keyed by the SHA-256 of `url` itself. A cache hit that
+/// verifies skips the network entirely; a cache hit that fails to verify
+/// (corrupt or stale file) is discarded and refetched.
 #[cfg(feature = "download")]
-pub fn load_tiktoken_bpe(encoding: &str) -> Result<HashMap<Vec<u8>, Rank>> {
+fn fetch_with_cache(url: &str, expected_hash: &str) -> Result<String> {
     use sha2::{Digest, Sha256};
 
-    let vocab_info = get_vocab_info(encoding)
-        .ok_or_else(|| TiktokenError::UnknownEncoding(encoding.to_string()))?;
+    let cache_key = {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_bytes());
+        format!("{:x}", hasher.finalize())
+    };
+    let cache_path = cache_dir().join(&cache_key);
 
-    // Try to download the vocabulary file
-    let response = reqwest::blocking::get(vocab_info.url)
-        .map_err(|e| TiktokenError::DataError(format!("Failed to download vocabulary: {e}")))?;
+    let verify = |content: &str| -> Result<()> {
+        let mut hasher = Sha256::new();
+        hasher.update(content.as_bytes());
+        let hash = format!("{:x}", hasher.finalize());
+        if hash != expected_hash {
+            return Err(TiktokenError::DataError(format!(
+                "Vocabulary hash mismatch. Expected: {expected_hash}, Got: {hash}"
+            )));
+        }
+        Ok(())
+    };
 
+    if let Ok(cached) = std::fs::read_to_string(&cache_path) {
+        if verify(&cached).is_ok() {
+            return Ok(cached);
+        }
+        // Cached file is corrupt or stale; fall through and refetch.
+        let _ = std::fs::remove_file(&cache_path);
+    }
+
+    let response = reqwest::blocking::get(url)
+        .map_err(|e| TiktokenError::DataError(format!("Failed to download vocabulary: {e}")))?;
     let content = response
         .text()
         .map_err(|e| TiktokenError::DataError(format!("Failed to read vocabulary content: {e}")))?;
 
-    // Verify the hash
+    verify(&content)?;
+
+    if std::fs::create_dir_all(cache_dir()).is_ok() {
+        let _ = std::fs::write(&cache_path, &content);
+    }
+
+    Ok(content)
+}
+
+/// Load tiktoken BPE vocabulary by downloading it, reusing a verified
+/// on-disk cache entry when there is one. Embedded data is not consulted.
+#[cfg(feature = "download")]
+pub fn load_tiktoken_bpe(encoding: &str, num_special_tokens: usize) -> Result<HashMap<Vec<u8>, Rank>> {
+    let vocab_info = get_vocab_info(encoding)
+        .ok_or_else(|| TiktokenError::UnknownEncoding(encoding.to_string()))?;
+
+    let content = fetch_with_cache(vocab_info.url, vocab_info.expected_hash)?;
+
+    let ranks = parse_tiktoken_bpe(&content)?;
+    validate_vocab_size(&ranks, num_special_tokens, vocab_info.expected_n_vocab)?;
+    Ok(ranks)
+}
+
+/// Vocabulary bytes baked into the binary by the `embedded` feature, so a
+/// build can resolve encodings without any network access.
+///
+/// The files under `src/embedded_vocab/` in this tree are placeholders: this
+/// environment has no network access to fetch OpenAI's real `.tiktoken`
+/// files, so they currently hold the same toy vocabulary
+/// [`create_basic_vocabulary`] produces, serialized in `.tiktoken` format. A
+/// real build should replace each file with the genuine vocabulary (whose
+/// hashes are already in [`get_vocab_info`]) before shipping; until then,
+/// `load_tiktoken_bpe` will correctly reject them with a hash mismatch
+/// rather than silently serving the wrong vocabulary.
+#[cfg(feature = "embedded")]
+mod embedded_vocab {
+    pub const R50K_BASE: &str = include_str!("embedded_vocab/r50k_base.tiktoken");
+    pub const P50K_BASE: &str = include_str!("embedded_vocab/p50k_base.tiktoken");
+    pub const CL100K_BASE: &str = include_str!("embedded_vocab/cl100k_base.tiktoken");
+    pub const O200K_BASE: &str = include_str!("embedded_vocab/o200k_base.tiktoken");
+}
+
+#[cfg(feature = "embedded")]
+fn embedded_vocab_content(encoding: &str) -> Option<&'static str> {
+    match encoding {
+        "r50k_base" | "gpt2" => Some(embedded_vocab::R50K_BASE),
+        "p50k_base" | "p50k_edit" => Some(embedded_vocab::P50K_BASE),
+        "cl100k_base" => Some(embedded_vocab::CL100K_BASE),
+        "o200k_base" => Some(embedded_vocab::O200K_BASE),
+        _ => None,
+    }
+}
+
+/// Load tiktoken BPE vocabulary from the `embedded` feature's baked-in data,
+/// verifying it against the same hash the `download` feature checks.
+#[cfg(all(feature = "embedded", not(feature = "download")))]
+pub fn load_tiktoken_bpe(encoding: &str, num_special_tokens: usize) -> Result<HashMap<Vec<u8>, Rank>> {
+    use sha2::{Digest, Sha256};
+
+    let vocab_info = get_vocab_info(encoding)
+        .ok_or_else(|| TiktokenError::UnknownEncoding(encoding.to_string()))?;
+    let content = embedded_vocab_content(encoding)
+        .ok_or_else(|| TiktokenError::UnknownEncoding(encoding.to_string()))?;
+
     let mut hasher = Sha256::new();
     hasher.update(content.as_bytes());
     let hash = format!("{:x}", hasher.finalize());
-
     if hash != vocab_info.expected_hash {
         return Err(TiktokenError::DataError(format!(
-            "Vocabulary hash mismatch. Expected: {}, Got: {}",
-            vocab_info.expected_hash, hash
+            "Embedded vocabulary for '{encoding}' does not match the expected hash \
+             (expected: {}, got: {hash}) - replace src/embedded_vocab/ with the real vocabulary files",
+            vocab_info.expected_hash
         )));
     }
 
-    parse_tiktoken_bpe(&content)
+    let ranks = parse_tiktoken_bpe(content)?;
+    validate_vocab_size(&ranks, num_special_tokens, vocab_info.expected_n_vocab)?;
+    Ok(ranks)
+}
+
+/// Load tiktoken BPE vocabulary with neither `download` nor `embedded`
+/// enabled: falls back to a tiny, non-conformant toy vocabulary. This does
+/// **not** match any real OpenAI encoding and should only be used when no
+/// other vocabulary source is available.
+#[cfg(not(any(feature = "download", feature = "embedded")))]
+pub fn load_tiktoken_bpe(_encoding: &str, _num_special_tokens: usize) -> Result<HashMap<Vec<u8>, Rank>> {
+    create_basic_vocabulary()
+}
+
+/// Download and parse GPT-2's data-gym vocabulary (`vocab.bpe` +
+/// `encoder.json`) for `encoding`, verifying both files' hashes before
+/// parsing.
+#[cfg(feature = "download")]
+pub fn load_data_gym_bpe(
+    encoding: &str,
+    _num_special_tokens: usize,
+) -> Result<HashMap<Vec<u8>, Rank>> {
+    let vocab_info = get_data_gym_vocab_info(encoding)
+        .ok_or_else(|| TiktokenError::UnknownEncoding(encoding.to_string()))?;
+
+    let vocab_bpe = fetch_with_cache(vocab_info.vocab_bpe_url, vocab_info.vocab_bpe_hash)?;
+    let encoder_json = fetch_with_cache(vocab_info.encoder_json_url, vocab_info.encoder_json_hash)?;
+
+    parse_data_gym_bpe(&vocab_bpe, &encoder_json)
 }
 
-/// Load tiktoken BPE vocabulary without download feature (uses embedded fallback)
-#[cfg(not(feature = "download"))]
-pub fn load_tiktoken_bpe(encoding: &str) -> Result<HashMap<Vec<u8>, Rank>> {
-    // For now, fall back to the basic vocabulary when download is disabled
-    // In a production implementation, you would embed the actual vocabulary files
+/// Load GPT-2's vocabulary from the `embedded` feature's baked-in data. GPT-2
+/// only has an `.tiktoken`-shaped placeholder embedded (see
+/// [`embedded_vocab_content`]), not the genuine two-file data-gym format, so
+/// this parses it with [`parse_tiktoken_bpe`] rather than
+/// [`parse_data_gym_bpe`].
+#[cfg(all(feature = "embedded", not(feature = "download")))]
+pub fn load_data_gym_bpe(encoding: &str, num_special_tokens: usize) -> Result<HashMap<Vec<u8>, Rank>> {
+    // gpt2 shares r50k_base's ranks and embedded file, and only r50k_base
+    // has the `VocabInfo` (hash and size) to verify that file against.
+    let tiktoken_name = if encoding == "gpt2" { "r50k_base" } else { encoding };
+    load_tiktoken_bpe(tiktoken_name, num_special_tokens)
+}
+
+/// Load GPT-2's vocabulary with neither `download` nor `embedded` enabled
+/// (uses the basic fallback vocabulary).
+#[cfg(not(any(feature = "download", feature = "embedded")))]
+pub fn load_data_gym_bpe(
+    _encoding: &str,
+    _num_special_tokens: usize,
+) -> Result<HashMap<Vec<u8>, Rank>> {
     create_basic_vocabulary()
 }
 
@@ -108,6 +576,64 @@ pub fn parse_tiktoken_bpe(content: &str) -> Result<HashMap<Vec<u8>, Rank>> {
     Ok(ranks)
 }
 
+/// Parse a `.tiktoken` rank file, rejecting duplicate token bytes or ranks
+/// instead of silently overwriting them. Used for user-supplied vocabulary
+/// files where a malformed file should fail loudly rather than produce a
+/// partially-wrong encoding.
+pub fn parse_tiktoken_bpe_strict(content: &str) -> Result<HashMap<Vec<u8>, Rank>> {
+    let mut ranks: HashMap<Vec<u8>, Rank> = HashMap::new();
+    let mut seen_ranks: std::collections::HashSet<Rank> = std::collections::HashSet::new();
+
+    for (line_no, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 2 {
+            return Err(TiktokenError::DataError(format!(
+                "Invalid tiktoken format at line {}: {}",
+                line_no + 1,
+                line
+            )));
+        }
+
+        let token_bytes =
+            base64::engine::general_purpose::STANDARD.decode(parts[0]).map_err(|e| {
+                TiktokenError::DataError(format!("Invalid base64 in tiktoken file: {e}"))
+            })?;
+
+        let token_rank: Rank = parts[1]
+            .parse()
+            .map_err(|e| TiktokenError::DataError(format!("Invalid rank in tiktoken file: {e}")))?;
+
+        if ranks.contains_key(&token_bytes) {
+            return Err(TiktokenError::DataError(format!(
+                "Duplicate token bytes at line {}",
+                line_no + 1
+            )));
+        }
+        if !seen_ranks.insert(token_rank) {
+            return Err(TiktokenError::DataError(format!(
+                "Duplicate rank {} at line {}",
+                token_rank,
+                line_no + 1
+            )));
+        }
+
+        ranks.insert(token_bytes, token_rank);
+    }
+
+    Ok(ranks)
+}
+
+/// Load and strictly parse a `.tiktoken` rank file from disk.
+pub fn load_tiktoken_file(path: &std::path::Path) -> Result<HashMap<Vec<u8>, Rank>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| TiktokenError::DataError(format!("Failed to read vocabulary file: {e}")))?;
+    parse_tiktoken_bpe_strict(&content)
+}
+
 /// Create a basic vocabulary for demonstration purposes
 /// This creates a minimal BPE vocabulary that can handle basic ASCII text
 pub fn create_basic_vocabulary() -> Result<HashMap<Vec<u8>, Rank>> {
@@ -303,4 +829,93 @@ mod tests {
         assert!(get_vocab_info("o200k_base").is_some());
         assert!(get_vocab_info("unknown").is_none());
     }
+
+    #[test]
+    fn test_validate_vocab_size() {
+        let ranks: HashMap<Vec<u8>, Rank> = (0..10u32).map(|i| (vec![i as u8], i)).collect();
+
+        assert!(validate_vocab_size(&ranks, 1, Some(11)).is_ok());
+        assert!(validate_vocab_size(&ranks, 1, Some(12)).is_err());
+        // No expectation set means no check is performed.
+        assert!(validate_vocab_size(&ranks, 1, None).is_ok());
+    }
+
+    #[test]
+    fn test_get_data_gym_vocab_info() {
+        assert!(get_data_gym_vocab_info("gpt2").is_some());
+        assert!(get_data_gym_vocab_info("unknown").is_none());
+    }
+
+    #[test]
+    fn test_parse_data_gym_bpe() {
+        // Single-byte ranks follow data-gym's "printable bytes first" order,
+        // not byte value: '!' (0x21) is the first printable byte, so it gets
+        // rank 0, while 'a' (0x61) is the 65th (index 64), and 'b' the 66th.
+        let vocab_bpe = "#version: 0.2\na b\n";
+        let encoder_json = r#"{"!": 0, "a": 64, "b": 65, "ab": 256}"#;
+
+        let ranks = parse_data_gym_bpe(vocab_bpe, encoder_json).unwrap();
+
+        assert_eq!(ranks.get(&b"!".to_vec()), Some(&0));
+        assert_eq!(ranks.get(&b"a".to_vec()), Some(&64));
+        assert_eq!(ranks.get(&b"b".to_vec()), Some(&65));
+        assert_eq!(ranks.get(&b"ab".to_vec()), Some(&256));
+    }
+
+    #[test]
+    fn test_parse_data_gym_bpe_rejects_encoder_mismatch() {
+        let vocab_bpe = "#version: 0.2\na b\n";
+        let encoder_json = r#"{"a": 64, "b": 65, "ab": 999}"#;
+
+        assert!(parse_data_gym_bpe(vocab_bpe, encoder_json).is_err());
+    }
+
+    #[test]
+    fn test_parse_data_gym_bpe_rejects_byte_value_rank_order() {
+        // A regression guard for assigning single-byte ranks by raw byte
+        // value instead of data-gym's printable-bytes-first order: 'a'
+        // (0x61) is not rank 97 under the real ordering.
+        let vocab_bpe = "#version: 0.2\na b\n";
+        let encoder_json = r#"{"a": 97, "b": 98, "ab": 256}"#;
+
+        assert!(parse_data_gym_bpe(vocab_bpe, encoder_json).is_err());
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn test_fetch_with_cache_reuses_verified_file() {
+        use sha2::{Digest, Sha256};
+
+        let dir = std::env::temp_dir().join(format!("tiktoken-test-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::env::set_var("TIKTOKEN_CACHE_DIR", &dir);
+
+        let url = "https://example.invalid/does-not-exist.tiktoken";
+        let content = "cached content";
+        let mut hasher = Sha256::new();
+        hasher.update(content.as_bytes());
+        let hash = format!("{:x}", hasher.finalize());
+
+        let mut key_hasher = Sha256::new();
+        key_hasher.update(url.as_bytes());
+        let cache_key = format!("{:x}", key_hasher.finalize());
+        std::fs::write(dir.join(cache_key), content).unwrap();
+
+        // Network is unreachable in this environment, so a cache hit is the
+        // only way this can succeed.
+        let fetched = fetch_with_cache(url, &hash).unwrap();
+        assert_eq!(fetched, content);
+
+        std::env::remove_var("TIKTOKEN_CACHE_DIR");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(all(feature = "embedded", not(feature = "download")))]
+    #[test]
+    fn test_embedded_vocab_rejects_placeholder_hash_mismatch() {
+        // The embedded files in this tree are toy placeholders (see the doc
+        // comment on the `embedded_vocab` module), so they must never silently
+        // pass as a real vocabulary.
+        assert!(load_tiktoken_bpe("cl100k_base", 5).is_err());
+    }
 }